@@ -1,6 +1,86 @@
+use crate::diagnostic::{Diagnostic, Diagnostics, Span};
+
+/// Whitespace separated word of a source line, `[...]` is a single word
+#[derive(Debug, Clone, Copy)]
+pub struct Token<'a> {
+    pub text: &'a str,
+    pub span: Span,
+}
+
+/// Split the line into tokens. `line` is the 1-based line number.
+fn tokenize(line: usize, src: &str) -> Vec<Token<'_>> {
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut start: Option<usize> = None;
+    let mut in_brackets = false;
+    for (idx, ch) in src.char_indices().chain([(src.len(), ' ')]) {
+        match ch {
+            '[' => in_brackets = true,
+            ']' => in_brackets = false,
+            _ => {}
+        }
+
+        // Whitespace inside of the brackets doesn't end the token
+        let is_separator = ch.is_ascii_whitespace() && !(in_brackets && idx < src.len());
+        match (is_separator, start) {
+            (true, Some(begin)) => {
+                let text = &src[begin..idx];
+                let column = src[..begin].chars().count() + 1;
+                let span = Span::new(line, column, text.chars().count());
+                tokens.push(Token { text, span });
+                start = None;
+            }
+            (false, None) => start = Some(idx),
+            _ => {}
+        }
+    }
+
+    tokens
+}
+
+/// Span right after the last token, used when arguments are missing
+fn end_span(tokens: &[Token]) -> Span {
+    let last = tokens[tokens.len() - 1].span;
+    Span::new(last.line, last.column + last.len, 1)
+}
+
+/// Span from the first token to the end of the last one
+fn tokens_span(tokens: &[Token]) -> Span {
+    let first = tokens[0].span;
+    let last = tokens[tokens.len() - 1].span;
+    Span::new(
+        first.line,
+        first.column,
+        last.column + last.len - first.column,
+    )
+}
+
+/// Make sure that there are exactly `count` arguments after the operator
+fn expect_args(tokens: &[Token], count: usize) -> Result<(), Diagnostic> {
+    // This includes the operator
+    let got = tokens.len() - 1;
+    if got < count {
+        let msg = format!("Expected {count} arguments, got {got}");
+        return Err(Diagnostic::error(end_span(tokens), msg));
+    }
+
+    if got > count {
+        let msg = format!("Expected {count} arguments, got {got}");
+        let extra = tokens_span(&tokens[count + 1..]);
+        return Err(Diagnostic::error(extra, msg).with_note("Remove the extra arguments"));
+    }
+
+    Ok(())
+}
+
+fn parse_operand<R: Register>(token: &Token) -> Result<R, Diagnostic> {
+    R::try_parse(token.text).map_err(|msg| Diagnostic::error(token.span, msg))
+}
+
+#[allow(dead_code)]
 trait Arg {
     fn args(self) -> Vec<RegType>;
-    fn try_parse(input: &str) -> Result<Self, String>
+    /// `tokens` includes the operator
+    fn try_parse(tokens: &[Token]) -> Result<Self, Diagnostic>
     where
         Self: Sized;
 }
@@ -8,23 +88,33 @@ trait Arg {
 #[derive(Debug)]
 pub struct Arg0 {}
 
-/* impl Arg for Arg0 {
+impl Arg for Arg0 {
     fn args(self) -> Vec<RegType> {
         Vec::new()
     }
-} */
+
+    fn try_parse(tokens: &[Token]) -> Result<Self, Diagnostic> {
+        expect_args(tokens, 0)?;
+        Ok(Self {})
+    }
+}
 
 #[derive(Debug)]
-#[allow(dead_code)]
-struct Arg1<A1: Register> {
-    arg1: A1,
+pub struct Arg1<A1: Register> {
+    pub arg1: A1,
 }
 
-/* impl<A1: Register> Arg for Arg1<A1> {
+impl<A1: Register> Arg for Arg1<A1> {
     fn args(self) -> Vec<RegType> {
         vec![self.arg1.parse()]
     }
-} */
+
+    fn try_parse(tokens: &[Token]) -> Result<Self, Diagnostic> {
+        expect_args(tokens, 1)?;
+        let arg1 = parse_operand(&tokens[1])?;
+        Ok(Self { arg1 })
+    }
+}
 
 #[derive(Debug)]
 pub struct Arg2<A1: Register, A2: Register> {
@@ -37,23 +127,10 @@ impl<A1: Register, A2: Register> Arg for Arg2<A1, A2> {
         vec![self.arg1.parse(), self.arg2.parse()]
     }
 
-    fn try_parse(input: &str) -> Result<Self, String> {
-        input.try_into()
-    }
-}
-
-impl<A1: Register, A2: Register> TryFrom<&str> for Arg2<A1, A2> {
-    type Error = String;
-
-    fn try_from(line: &str) -> Result<Self, Self::Error> {
-        let items: Vec<&str> = line.split_ascii_whitespace().collect();
-        // This includes the operator
-        if items.len() < 3 {
-            return Err(format!("Exected 2 arguments got {}", items.len() - 1));
-        }
-
-        let arg1 = A1::try_parse(items[1])?;
-        let arg2 = A2::try_parse(items[2])?;
+    fn try_parse(tokens: &[Token]) -> Result<Self, Diagnostic> {
+        expect_args(tokens, 2)?;
+        let arg1 = parse_operand(&tokens[1])?;
+        let arg2 = parse_operand(&tokens[2])?;
         Ok(Self { arg1, arg2 })
     }
 }
@@ -74,9 +151,13 @@ pub enum R8Regs {
 pub enum R16Regs {
     L0,
     L1,
+    Vp,
+    Cr,
+    Sp,
 }
 
 #[derive(Debug)]
+#[allow(dead_code)]
 pub enum RegType {
     /// 8-bit register
     R8(R8Regs),
@@ -88,6 +169,7 @@ pub enum RegType {
     I16(u16),
 }
 
+#[allow(dead_code)]
 pub trait Register {
     fn parse(self) -> RegType;
     fn try_parse(input: &str) -> Result<Self, String>
@@ -114,11 +196,10 @@ impl TryFrom<&str> for R8 {
             return fail(value);
         }
 
-        // TODO: handle the ascii values properly
-        let reg = val.as_bytes()[1] - 48;
-        if reg > 7 {
-            return fail(value);
-        }
+        let reg = match (val.as_bytes()[1] as char).to_digit(10) {
+            Some(reg) if reg <= 7 => reg,
+            _ => return fail(value),
+        };
 
         let register = match reg {
             0 => R8Regs::R0,
@@ -186,23 +267,16 @@ impl TryFrom<&str> for R16 {
         let val = value.trim();
 
         fn fail(value: &str) -> Result<R16, String> {
-            Err(format!("Expected l0-1, received {value}"))
+            Err(format!("Expected l0-1, vp, cr or sp, received {value}"))
         }
 
-        if val.len() != 2 || val.as_bytes()[0] != b'l' {
-            return fail(value);
-        }
-
-        // TODO: handle the ascii values properly
-        let reg = val.as_bytes()[1] - 48;
-        if reg > 1 {
-            return fail(value);
-        }
-
-        let register = match reg {
-            0 => R16Regs::L0,
-            1 => R16Regs::L1,
-            _ => unreachable!(),
+        let register = match val {
+            "l0" => R16Regs::L0,
+            "l1" => R16Regs::L1,
+            "vp" => R16Regs::Vp,
+            "cr" => R16Regs::Cr,
+            "sp" => R16Regs::Sp,
+            _ => return fail(value),
         };
 
         Ok(Self { register })
@@ -276,16 +350,119 @@ impl Register for A16 {
     }
 }
 
+/// Offset added to the base register of an indirect memory operand
+#[derive(Debug, Clone, Copy)]
+pub enum Offset {
+    /// `[base]`
+    None,
+    /// `[base + index]`, register in the 4-bit encoding
+    Register(u8),
+    /// `[base + imm]`
+    Add(u8),
+    /// `[base - imm]`
+    Sub(u8),
+}
+
+/// Memory operand, either an absolute address or an address relative to a register:
+/// `[base]`, `[base + index]`, `[base + imm]` or `[base - imm]`
+#[derive(Debug)]
+pub enum M16 {
+    Absolute(A16),
+    Indirect { base: u8, offset: Offset },
+}
+
+impl M16 {
+    pub fn is_indirect(&self) -> bool {
+        matches!(self, Self::Indirect { .. })
+    }
+}
+
+/// 4-bit encoding of any register that can be used in an address
+fn address_register(name: &str) -> Result<u8, String> {
+    let reg = match name {
+        "r0" => 0b0000,
+        "r1" => 0b0001,
+        "r2" => 0b0010,
+        "r3" => 0b0011,
+        "r4" => 0b0100,
+        "r5" => 0b0101,
+        "r6" => 0b0110,
+        "r7" => 0b0111,
+        "vp" => 0b1000,
+        "l0" => 0b1001,
+        "l1" => 0b1010,
+        "cr" => 0b1101,
+        "sp" => 0b1110,
+        _ => {
+            return Err(format!(
+                "Expected a register in the address, received {name}"
+            ))
+        }
+    };
+
+    Ok(reg)
+}
+
+impl TryFrom<&str> for M16 {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let val = value.trim();
+        let Some(inner) = val.strip_prefix('[') else {
+            return Ok(Self::Absolute(val.try_into()?));
+        };
+        let Some(inner) = inner.strip_suffix(']') else {
+            return Err(format!("Expected ']' at the end of the address {val}"));
+        };
+
+        let offset_imm = |imm: &str| {
+            imm.trim()
+                .parse::<u8>()
+                .map_err(|_| format!("Expected 0-255 offset, got {}", imm.trim()))
+        };
+
+        let (base, offset) = if let Some((base, index)) = inner.split_once('+') {
+            let index = index.trim();
+            let offset = match address_register(index) {
+                Ok(reg) => Offset::Register(reg),
+                Err(_) => Offset::Add(offset_imm(index)?),
+            };
+            (base, offset)
+        } else if let Some((base, imm)) = inner.split_once('-') {
+            (base, Offset::Sub(offset_imm(imm)?))
+        } else {
+            (inner, Offset::None)
+        };
+
+        let base = address_register(base.trim())?;
+        Ok(Self::Indirect { base, offset })
+    }
+}
+
+impl Register for M16 {
+    // Parse should be not used
+    fn parse(self) -> RegType {
+        match self {
+            Self::Absolute(addr) => addr.parse(),
+            Self::Indirect { .. } => RegType::I16(0),
+        }
+    }
+
+    fn try_parse(input: &str) -> Result<Self, String> {
+        input.try_into()
+    }
+}
+
 #[derive(Debug)]
 #[allow(dead_code)]
 pub struct InstrLine<T> {
     instr: T,
-    line: usize,
+    span: Span,
 }
 
 impl<T> InstrLine<T> {
-    fn new(instr: T, line: usize) -> Self {
-        Self { instr, line }
+    fn new(instr: T, span: Span) -> Self {
+        Self { instr, span }
     }
 
     pub fn inner(&self) -> &T {
@@ -293,37 +470,123 @@ impl<T> InstrLine<T> {
     }
 }
 
+/// Label or variable name with its location in the source
 #[derive(Debug)]
+pub struct Ident {
+    pub name: String,
+    pub span: Span,
+}
+
+impl Ident {
+    fn new(token: &Token) -> Self {
+        Self {
+            name: token.text.into(),
+            span: token.span,
+        }
+    }
+}
+
+#[derive(Debug)]
+#[allow(dead_code)]
 pub enum Instruction {
     Add(InstrLine<Arg2<R8, R8>>),
     AddI(InstrLine<Arg2<R8, I8>>),
+    AddL(InstrLine<Arg2<R16, R16>>),
+    AddIL(InstrLine<Arg2<R16, I16>>),
+    Sub(InstrLine<Arg2<R8, R8>>),
+    SubI(InstrLine<Arg2<R8, I8>>),
+    SubL(InstrLine<Arg2<R16, R16>>),
+    SubIL(InstrLine<Arg2<R16, I16>>),
+    And(InstrLine<Arg2<R8, R8>>),
+    AndI(InstrLine<Arg2<R8, I8>>),
+    AndL(InstrLine<Arg2<R16, R16>>),
+    AndIL(InstrLine<Arg2<R16, I16>>),
+    Or(InstrLine<Arg2<R8, R8>>),
+    OrI(InstrLine<Arg2<R8, I8>>),
+    OrL(InstrLine<Arg2<R16, R16>>),
+    OrIL(InstrLine<Arg2<R16, I16>>),
+    Xor(InstrLine<Arg2<R8, R8>>),
+    XorI(InstrLine<Arg2<R8, I8>>),
+    XorL(InstrLine<Arg2<R16, R16>>),
+    XorIL(InstrLine<Arg2<R16, I16>>),
+    Not(InstrLine<Arg1<R8>>),
+    NotL(InstrLine<Arg1<R16>>),
+    Inc(InstrLine<Arg1<R8>>),
+    IncL(InstrLine<Arg1<R16>>),
+    Dec(InstrLine<Arg1<R8>>),
+    DecL(InstrLine<Arg1<R16>>),
     EqR(InstrLine<Arg2<R8, R8>>),
     EqI(InstrLine<Arg2<R8, I8>>),
     EqRL(InstrLine<Arg2<R16, R16>>),
     EqIL(InstrLine<Arg2<R16, I16>>),
 
+    Mul(InstrLine<Arg2<R8, R8>>),
+    MulI(InstrLine<Arg2<R8, I8>>),
+    MulL(InstrLine<Arg2<R16, R16>>),
+    MulIL(InstrLine<Arg2<R16, I16>>),
+    Div(InstrLine<Arg2<R8, R8>>),
+    DivI(InstrLine<Arg2<R8, I8>>),
+    DivL(InstrLine<Arg2<R16, R16>>),
+    DivIL(InstrLine<Arg2<R16, I16>>),
+    Mod(InstrLine<Arg2<R8, R8>>),
+    ModI(InstrLine<Arg2<R8, I8>>),
+    ModL(InstrLine<Arg2<R16, R16>>),
+    ModIL(InstrLine<Arg2<R16, I16>>),
+    Shl(InstrLine<Arg2<R8, R8>>),
+    ShlI(InstrLine<Arg2<R8, I8>>),
+    ShlL(InstrLine<Arg2<R16, R16>>),
+    ShlIL(InstrLine<Arg2<R16, I16>>),
+    Shr(InstrLine<Arg2<R8, R8>>),
+    ShrI(InstrLine<Arg2<R8, I8>>),
+    ShrL(InstrLine<Arg2<R16, R16>>),
+    ShrIL(InstrLine<Arg2<R16, I16>>),
+    Sar(InstrLine<Arg2<R8, R8>>),
+    SarI(InstrLine<Arg2<R8, I8>>),
+    SarL(InstrLine<Arg2<R16, R16>>),
+    SarIL(InstrLine<Arg2<R16, I16>>),
+
     St(InstrLine<Arg2<R8, R8>>),
     StL(InstrLine<Arg2<R16, R16>>),
     StI(InstrLine<Arg2<R8, I8>>),
     StIL(InstrLine<Arg2<R16, I16>>),
-    Stm(InstrLine<Arg2<A16, I8>>),
-    StmL(InstrLine<Arg2<A16, I16>>),
-    Str(InstrLine<Arg2<A16, R8>>),
-    StrL(InstrLine<Arg2<A16, R16>>),
-    Ldm(InstrLine<Arg2<A16, R8>>),
-    LdmL(InstrLine<Arg2<A16, R16>>),
-
-    Be(InstrLine<String>),
-    Bne(InstrLine<String>),
-    Bgt(InstrLine<String>),
-    Blt(InstrLine<String>),
-    Call(InstrLine<String>),
+    Stm(InstrLine<Arg2<M16, I8>>),
+    StmL(InstrLine<Arg2<M16, I16>>),
+    Str(InstrLine<Arg2<M16, R8>>),
+    StrL(InstrLine<Arg2<M16, R16>>),
+    Ldm(InstrLine<Arg2<M16, R8>>),
+    LdmL(InstrLine<Arg2<M16, R16>>),
+    Swp(InstrLine<Arg2<R8, R8>>),
+    SwpL(InstrLine<Arg2<R16, R16>>),
+    SwpM(InstrLine<Arg2<M16, R8>>),
+    SwpML(InstrLine<Arg2<M16, R16>>),
+
+    Jmp(InstrLine<Ident>),
+    JmpR(InstrLine<Arg1<R16>>),
+    Be(InstrLine<Ident>),
+    Bne(InstrLine<Ident>),
+    Bz(InstrLine<Ident>),
+    Bnz(InstrLine<Ident>),
+    Bc(InstrLine<Ident>),
+    Bnc(InstrLine<Ident>),
+    Bo(InstrLine<Ident>),
+    Bno(InstrLine<Ident>),
+    Bs(InstrLine<Ident>),
+    Bns(InstrLine<Ident>),
+    Berr(InstrLine<Ident>),
+    Bnerr(InstrLine<Ident>),
+    Bgt(InstrLine<Ident>),
+    Blt(InstrLine<Ident>),
+    Call(InstrLine<Ident>),
+    CallR(InstrLine<Arg1<R16>>),
     Ret(InstrLine<Arg0>),
+    Reti(InstrLine<Arg0>),
+    Ei(InstrLine<Arg0>),
+    Di(InstrLine<Arg0>),
     Syscall(InstrLine<Arg0>),
-    Sv(InstrLine<String>),
+    Sv(InstrLine<Ident>),
     Uv(InstrLine<Arg0>),
 
-    Label(String),
+    Label(Ident),
 }
 
 #[derive(Debug)]
@@ -331,6 +594,8 @@ pub struct Variable {
     pub name: String,
     pub size: u16,
     pub bytes: Option<Vec<u8>>,
+    /// Location of the variable name
+    pub span: Span,
 }
 
 #[derive(Debug)]
@@ -339,253 +604,283 @@ pub struct ASTTree {
     pub instructions: Vec<Instruction>,
 }
 
-fn parse_variable_value(value: &str) -> Vec<u8> {
+/// `value` is the rest of the line starting from `column`
+fn parse_variable_value(line: usize, column: usize, value: &str) -> Result<Vec<u8>, Diagnostic> {
     let mut bytes: Vec<u8> = Vec::new();
-    if value.starts_with('"') {
-        let mut closed = false;
-        let chars = value.as_bytes();
-        let mut idx = 1; // skip first "
-        while idx < chars.len() {
-            let byte = chars[idx];
-            // TODO: properly handle escaping
-            if byte == b'\\' && chars[idx + 1] == b'n' {
-                bytes.push(b'\n');
-                idx += 2;
-                continue;
-            }
-
-            if byte == b'"' {
-                closed = true;
-                break;
-            }
+    let value_span = Span::new(line, column, value.chars().count());
+    if !value.starts_with('"') {
+        return Err(Diagnostic::error(
+            value_span,
+            "Currently only string literal variables are suppored",
+        ));
+    }
 
-            bytes.push(byte);
-            idx += 1;
+    let mut closed = false;
+    let mut chars = value.char_indices().skip(1); // skip first "
+    while let Some((idx, ch)) = chars.next() {
+        if ch == '\\' {
+            let escaped = match chars.next() {
+                Some((_, 'n')) => '\n',
+                Some((_, 't')) => '\t',
+                Some((_, '0')) => '\0',
+                Some((_, '\\')) => '\\',
+                Some((_, '"')) => '"',
+                other => {
+                    let len = 1 + other.map_or(0, |_| 1);
+                    let span = Span::new(line, column + value[..idx].chars().count(), len);
+                    return Err(Diagnostic::error(span, "Unknown escape sequence")
+                        .with_note("Supported escapes are \\n, \\t, \\0, \\\\ and \\\""));
+                }
+            };
+            bytes.push(escaped as u8);
+            continue;
         }
 
-        if !closed {
-            panic!("Unclosed string");
+        if ch == '"' {
+            closed = true;
+            let rest = value[idx + 1..].trim();
+            if !rest.is_empty() && !rest.starts_with('#') {
+                let after = &value[idx + 1..];
+                let spaces = after.len() - after.trim_start().len();
+                let rest_column = column + value[..=idx].chars().count() + spaces;
+                let span = Span::new(line, rest_column, rest.chars().count());
+                return Err(Diagnostic::error(
+                    span,
+                    "Unexpected tokens after the string",
+                ));
+            }
+            break;
         }
-    } else {
-        panic!("Currently only string literal variables are suppored");
+
+        let mut buf = [0; 4];
+        bytes.extend(ch.encode_utf8(&mut buf).as_bytes());
     }
 
-    bytes
+    if !closed {
+        let span = Span::new(line, column, 1);
+        return Err(Diagnostic::error(span, "Unclosed string").with_note("Add a closing '\"'"));
+    }
+
+    Ok(bytes)
 }
 
-fn parse_variable_line(line: &str) -> Variable {
-    let mut items = line.split_ascii_whitespace();
-    let name = items.next().unwrap();
-    let size_str = items.next().expect("Variable needs a size");
-    let rest: String = items.collect::<Vec<&str>>().join(" ");
-    let bytes = if !rest.is_empty() {
-        Some(parse_variable_value(&rest))
-    } else {
-        None
+fn parse_variable_line(line: usize, src: &str) -> Result<Variable, Diagnostic> {
+    let tokens = tokenize(line, src);
+    let name = &tokens[0];
+    let Some(size_token) = tokens.get(1) else {
+        return Err(
+            Diagnostic::error(end_span(&tokens), "Variable needs a size")
+                .with_note(format!("Declare the variable as '{} <size>'", name.text)),
+        );
     };
 
-    let size = size_str.parse::<u16>().unwrap();
+    let size = size_token.text.parse::<u16>().map_err(|_| {
+        let msg = format!("Expected 0-65535 size, got {}", size_token.text);
+        Diagnostic::error(size_token.span, msg)
+    })?;
+
+    let bytes = match tokens.get(2) {
+        // Take the rest of the line as is so spaces in strings are kept
+        Some(value) => {
+            let start = src
+                .char_indices()
+                .nth(value.span.column - 1)
+                .map_or(src.len(), |(idx, _)| idx);
+            Some(parse_variable_value(
+                line,
+                value.span.column,
+                &src[start..],
+            )?)
+        }
+        None => None,
+    };
 
-    Variable {
-        name: name.into(),
+    Ok(Variable {
+        name: name.text.into(),
         size,
         bytes,
-    }
+        span: name.span,
+    })
 }
 
-fn parse_variables(src: &str) -> Vec<Variable> {
-    let mut variables: Vec<Variable> = Vec::new();
-
-    let mut var_start = false;
-    for line in src.lines() {
-        if line.starts_with("---") {
-            if !var_start {
-                var_start = true;
-            } else if var_start {
-                break;
+fn parse_args<T: Arg>(tokens: &[Token]) -> Result<InstrLine<T>, Diagnostic> {
+    Ok(InstrLine::new(T::try_parse(tokens)?, tokens_span(tokens)))
+}
+
+fn parse_ident(tokens: &[Token]) -> Result<InstrLine<Ident>, Diagnostic> {
+    expect_args(tokens, 1)?;
+    Ok(InstrLine::new(Ident::new(&tokens[1]), tokens_span(tokens)))
+}
+
+/// Jump and call take a 16-bit register instead of a label for indirect targets
+fn is_register_target(tokens: &[Token]) -> bool {
+    tokens.len() == 2 && R16::try_from(tokens[1].text).is_ok()
+}
+
+fn parse_instruction_line(line: usize, src: &str) -> Result<Instruction, Diagnostic> {
+    // Instructions have no strings, so `#` always starts a trailing comment
+    let src = src.split('#').next().unwrap_or(src);
+    let tokens = tokenize(line, src);
+    let instr = tokens[0].text.to_lowercase();
+
+    let instruction = match instr.as_str() {
+        "add" => Instruction::Add(parse_args(&tokens)?),
+        "addi" => Instruction::AddI(parse_args(&tokens)?),
+        "addl" => Instruction::AddL(parse_args(&tokens)?),
+        "addil" => Instruction::AddIL(parse_args(&tokens)?),
+        "sub" => Instruction::Sub(parse_args(&tokens)?),
+        "subi" => Instruction::SubI(parse_args(&tokens)?),
+        "subl" => Instruction::SubL(parse_args(&tokens)?),
+        "subil" => Instruction::SubIL(parse_args(&tokens)?),
+        "and" => Instruction::And(parse_args(&tokens)?),
+        "andi" => Instruction::AndI(parse_args(&tokens)?),
+        "andl" => Instruction::AndL(parse_args(&tokens)?),
+        "andil" => Instruction::AndIL(parse_args(&tokens)?),
+        "or" => Instruction::Or(parse_args(&tokens)?),
+        "ori" => Instruction::OrI(parse_args(&tokens)?),
+        "orl" => Instruction::OrL(parse_args(&tokens)?),
+        "oril" => Instruction::OrIL(parse_args(&tokens)?),
+        "xor" => Instruction::Xor(parse_args(&tokens)?),
+        "xori" => Instruction::XorI(parse_args(&tokens)?),
+        "xorl" => Instruction::XorL(parse_args(&tokens)?),
+        "xoril" => Instruction::XorIL(parse_args(&tokens)?),
+        "not" => Instruction::Not(parse_args(&tokens)?),
+        "notl" => Instruction::NotL(parse_args(&tokens)?),
+        "inc" => Instruction::Inc(parse_args(&tokens)?),
+        "incl" => Instruction::IncL(parse_args(&tokens)?),
+        "dec" => Instruction::Dec(parse_args(&tokens)?),
+        "decl" => Instruction::DecL(parse_args(&tokens)?),
+        "eqr" => Instruction::EqR(parse_args(&tokens)?),
+        "eqi" => Instruction::EqI(parse_args(&tokens)?),
+        "eqil" => Instruction::EqIL(parse_args(&tokens)?),
+        "eqrl" => Instruction::EqRL(parse_args(&tokens)?),
+        "mul" => Instruction::Mul(parse_args(&tokens)?),
+        "muli" => Instruction::MulI(parse_args(&tokens)?),
+        "mull" => Instruction::MulL(parse_args(&tokens)?),
+        "mulil" => Instruction::MulIL(parse_args(&tokens)?),
+        "div" => Instruction::Div(parse_args(&tokens)?),
+        "divi" => Instruction::DivI(parse_args(&tokens)?),
+        "divl" => Instruction::DivL(parse_args(&tokens)?),
+        "divil" => Instruction::DivIL(parse_args(&tokens)?),
+        "mod" => Instruction::Mod(parse_args(&tokens)?),
+        "modi" => Instruction::ModI(parse_args(&tokens)?),
+        "modl" => Instruction::ModL(parse_args(&tokens)?),
+        "modil" => Instruction::ModIL(parse_args(&tokens)?),
+        "shl" => Instruction::Shl(parse_args(&tokens)?),
+        "shli" => Instruction::ShlI(parse_args(&tokens)?),
+        "shll" => Instruction::ShlL(parse_args(&tokens)?),
+        "shlil" => Instruction::ShlIL(parse_args(&tokens)?),
+        "shr" => Instruction::Shr(parse_args(&tokens)?),
+        "shri" => Instruction::ShrI(parse_args(&tokens)?),
+        "shrl" => Instruction::ShrL(parse_args(&tokens)?),
+        "shril" => Instruction::ShrIL(parse_args(&tokens)?),
+        "sar" => Instruction::Sar(parse_args(&tokens)?),
+        "sari" => Instruction::SarI(parse_args(&tokens)?),
+        "sarl" => Instruction::SarL(parse_args(&tokens)?),
+        "saril" => Instruction::SarIL(parse_args(&tokens)?),
+        "st" => Instruction::St(parse_args(&tokens)?),
+        "stl" => Instruction::StL(parse_args(&tokens)?),
+        "sti" => Instruction::StI(parse_args(&tokens)?),
+        "stil" => Instruction::StIL(parse_args(&tokens)?),
+        "stm" => Instruction::Stm(parse_args(&tokens)?),
+        "stml" => Instruction::StmL(parse_args(&tokens)?),
+        "str" => Instruction::Str(parse_args(&tokens)?),
+        "strl" => Instruction::StrL(parse_args(&tokens)?),
+        "ldm" => Instruction::Ldm(parse_args(&tokens)?),
+        "ldml" => Instruction::LdmL(parse_args(&tokens)?),
+        "swp" => Instruction::Swp(parse_args(&tokens)?),
+        "swpl" => Instruction::SwpL(parse_args(&tokens)?),
+        "swpm" => Instruction::SwpM(parse_args(&tokens)?),
+        "swpml" => Instruction::SwpML(parse_args(&tokens)?),
+        "syscall" => Instruction::Syscall(parse_args(&tokens)?),
+        "be" => Instruction::Be(parse_ident(&tokens)?),
+        "bne" => Instruction::Bne(parse_ident(&tokens)?),
+        "bz" => Instruction::Bz(parse_ident(&tokens)?),
+        "bnz" => Instruction::Bnz(parse_ident(&tokens)?),
+        "bc" => Instruction::Bc(parse_ident(&tokens)?),
+        "bnc" => Instruction::Bnc(parse_ident(&tokens)?),
+        "bo" => Instruction::Bo(parse_ident(&tokens)?),
+        "bno" => Instruction::Bno(parse_ident(&tokens)?),
+        "bs" => Instruction::Bs(parse_ident(&tokens)?),
+        "bns" => Instruction::Bns(parse_ident(&tokens)?),
+        "berr" => Instruction::Berr(parse_ident(&tokens)?),
+        "bnerr" => Instruction::Bnerr(parse_ident(&tokens)?),
+        "blt" => Instruction::Blt(parse_ident(&tokens)?),
+        "bgt" => Instruction::Bgt(parse_ident(&tokens)?),
+        "jmp" if is_register_target(&tokens) => Instruction::JmpR(parse_args(&tokens)?),
+        "jmp" => Instruction::Jmp(parse_ident(&tokens)?),
+        "call" if is_register_target(&tokens) => Instruction::CallR(parse_args(&tokens)?),
+        "call" => Instruction::Call(parse_ident(&tokens)?),
+        "ret" => Instruction::Ret(parse_args(&tokens)?),
+        "reti" => Instruction::Reti(parse_args(&tokens)?),
+        "ei" => Instruction::Ei(parse_args(&tokens)?),
+        "di" => Instruction::Di(parse_args(&tokens)?),
+        "uv" => Instruction::Uv(parse_args(&tokens)?),
+        "sv" => Instruction::Sv(parse_ident(&tokens)?),
+        label if label.ends_with(':') => {
+            if tokens.len() > 1 {
+                let msg = "Unexpected tokens after the label";
+                return Err(Diagnostic::error(tokens_span(&tokens[1..]), msg)
+                    .with_note("Instructions go on their own line after the label"));
             }
-            continue;
-        }
 
-        if !var_start {
-            continue;
+            // Remove the ':'
+            let token = &tokens[0];
+            Instruction::Label(Ident {
+                name: label[..label.len() - 1].into(),
+                span: Span::new(token.span.line, token.span.column, token.span.len - 1),
+            })
+        }
+        _ => {
+            let msg = format!("Instruction '{instr}' has not been implemented");
+            return Err(Diagnostic::error(tokens[0].span, msg));
         }
+    };
+
+    Ok(instruction)
+}
 
-        if line.starts_with('#') {
+/// Line is a comment or empty
+fn is_blank(line: &str) -> bool {
+    let line = line.trim();
+    line.is_empty() || line.starts_with('#')
+}
+
+/// Parse the whole source. Errors are collected into `diagnostics`
+/// and the invalid lines are left out of the tree.
+///
+/// Variables are declared between two `---` lines, everything outside of
+/// them is instructions.
+pub fn parse_source(source: &str, diagnostics: &mut Diagnostics) -> ASTTree {
+    let mut variables: Vec<Variable> = Vec::new();
+    let mut instructions: Vec<Instruction> = Vec::new();
+
+    // 0 = before variables, 1 = in variables, 2 = after variables
+    let mut section = 0;
+    for (idx, src) in source.lines().enumerate() {
+        let line = idx + 1;
+        if src.starts_with("---") && section < 2 {
+            section += 1;
             continue;
         }
 
-        if line.trim().is_empty() {
+        if is_blank(src) {
             continue;
         }
 
-        let var = parse_variable_line(line);
-        variables.push(var);
-    }
-
-    variables
-}
-
-fn parse_instruction_line(idx: usize, line: &str) -> Result<Instruction, String> {
-    let instr = line.split_ascii_whitespace().next().unwrap().to_lowercase();
-
-    if instr == "add" {
-        Ok(Instruction::Add(InstrLine::new(
-            Arg2::<R8, R8>::try_parse(line)?,
-            idx,
-        )))
-    } else if instr == "addi" {
-        Ok(Instruction::AddI(InstrLine::new(
-            Arg2::<R8, I8>::try_parse(line)?,
-            idx,
-        )))
-    } else if instr == "eqr" {
-        Ok(Instruction::EqR(InstrLine::new(
-            Arg2::<R8, R8>::try_parse(line)?,
-            idx,
-        )))
-    } else if instr == "eqi" {
-        Ok(Instruction::EqI(InstrLine::new(
-            Arg2::<R8, I8>::try_parse(line)?,
-            idx,
-        )))
-    } else if instr == "eqil" {
-        Ok(Instruction::EqIL(InstrLine::new(
-            Arg2::<R16, I16>::try_parse(line)?,
-            idx,
-        )))
-    } else if instr == "eqrl" {
-        Ok(Instruction::EqRL(InstrLine::new(
-            Arg2::<R16, R16>::try_parse(line)?,
-            idx,
-        )))
-    } else if instr == "st" {
-        Ok(Instruction::St(InstrLine::new(
-            Arg2::<R8, R8>::try_parse(line)?,
-            idx,
-        )))
-    } else if instr == "stl" {
-        Ok(Instruction::StL(InstrLine::new(
-            Arg2::<R16, R16>::try_parse(line)?,
-            idx,
-        )))
-    } else if instr == "sti" {
-        Ok(Instruction::StI(InstrLine::new(
-            Arg2::<R8, I8>::try_parse(line)?,
-            idx,
-        )))
-    } else if instr == "stil" {
-        Ok(Instruction::StIL(InstrLine::new(
-            Arg2::<R16, I16>::try_parse(line)?,
-            idx,
-        )))
-    } else if instr == "stm" {
-        Ok(Instruction::Stm(InstrLine::new(
-            Arg2::<A16, I8>::try_parse(line)?,
-            idx,
-        )))
-    } else if instr == "stml" {
-        Ok(Instruction::StmL(InstrLine::new(
-            Arg2::<A16, I16>::try_parse(line)?,
-            idx,
-        )))
-    } else if instr == "str" {
-        Ok(Instruction::Str(InstrLine::new(
-            Arg2::<A16, R8>::try_parse(line)?,
-            idx,
-        )))
-    } else if instr == "strl" {
-        Ok(Instruction::StrL(InstrLine::new(
-            Arg2::<A16, R16>::try_parse(line)?,
-            idx,
-        )))
-    } else if instr == "ldm" {
-        Ok(Instruction::Ldm(InstrLine::new(
-            Arg2::<A16, R8>::try_parse(line)?,
-            idx,
-        )))
-    } else if instr == "ldml" {
-        Ok(Instruction::LdmL(InstrLine::new(
-            Arg2::<A16, R16>::try_parse(line)?,
-            idx,
-        )))
-    } else if instr == "syscall" {
-        Ok(Instruction::Syscall(InstrLine::new(Arg0 {}, idx)))
-    } else if instr == "be" {
-        let args: Vec<&str> = line.split_ascii_whitespace().collect();
-        if args.len() < 2 {
-            return Err("BE requires an argument".into());
-        }
-        Ok(Instruction::Be(InstrLine::new(args[1].into(), idx)))
-    } else if instr == "bne" {
-        let args: Vec<&str> = line.split_ascii_whitespace().collect();
-        if args.len() < 2 {
-            return Err("BNE requires an argument".into());
-        }
-        Ok(Instruction::Bne(InstrLine::new(args[1].into(), idx)))
-    } else if instr == "blt" {
-        let args: Vec<&str> = line.split_ascii_whitespace().collect();
-        if args.len() < 2 {
-            return Err("BLT requires an argument".into());
-        }
-        Ok(Instruction::Blt(InstrLine::new(args[1].into(), idx)))
-    } else if instr == "bgt" {
-        let args: Vec<&str> = line.split_ascii_whitespace().collect();
-        if args.len() < 2 {
-            return Err("BGT requires an argument".into());
-        }
-        Ok(Instruction::Bgt(InstrLine::new(args[1].into(), idx)))
-    } else if instr == "call" {
-        let args: Vec<&str> = line.split_ascii_whitespace().collect();
-        if args.len() < 2 {
-            return Err("CALL requires an argument".into());
-        }
-        Ok(Instruction::Call(InstrLine::new(args[1].into(), idx)))
-    } else if instr == "ret" {
-        Ok(Instruction::Ret(InstrLine::new(Arg0 {}, idx)))
-    } else if instr == "uv" {
-        Ok(Instruction::Uv(InstrLine::new(Arg0 {}, idx)))
-    } else if instr == "sv" {
-        let args: Vec<&str> = line.split_ascii_whitespace().collect();
-        if args.len() < 2 {
-            panic!("SV requires an argument");
-        }
-        Ok(Instruction::Sv(InstrLine::new(args[1].into(), idx)))
-    } else if instr.ends_with(':') {
-        let args: Vec<&str> = line.split_ascii_whitespace().collect();
-        if args.len() > 1 {
-            return Err(format!(
-                "On line: {idx}: Instruction '{instr}' has not been implemented"
-            ));
+        let parsed = if section == 1 {
+            parse_variable_line(line, src).map(|var| variables.push(var))
+        } else {
+            parse_instruction_line(line, src).map(|instr| instructions.push(instr))
+        };
+
+        if let Err(diag) = parsed {
+            diagnostics.push(diag);
         }
-        // Remove the ':'
-        Ok(Instruction::Label(instr[..instr.len() - 1].into()))
-    } else {
-        Err(format!(
-            "On line: {idx}: Instruction '{instr}' has not been implemented"
-        ))
     }
-}
-
-pub fn parse_source(source: &str) -> Result<ASTTree, String> {
-    let variables = parse_variables(source);
-    let var_end = if let Some(idx) = source.rfind('-') {
-        idx + 1
-    } else {
-        0
-    };
-
-    let instructions: Vec<Instruction> = source[var_end..]
-        .lines()
-        .enumerate()
-        .map(|(idx, line)| (idx, line.trim()))
-        .filter(|(_, line)| !line.is_empty())
-        .filter(|(_, line)| !line.starts_with('#'))
-        .map(|(idx, line)| parse_instruction_line(idx, line).unwrap())
-        .collect();
 
-    Ok(ASTTree {
+    ASTTree {
         variables,
         instructions,
-    })
+    }
 }
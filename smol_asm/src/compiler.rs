@@ -1,11 +1,29 @@
-use smol_file::{SmolFile, Storage, StorageItem};
+use smol_file::{memory, SmolFile, Storage, StorageItem, Symbol};
 
-use crate::ast::{ASTTree, Arg2, Instruction, R16Regs, R8Regs, Variable, A16, I16, I8, R16, R8};
+use crate::{
+    ast::{
+        ASTTree, Arg1, Arg2, Ident, Instruction, Offset, R16Regs, R8Regs, Variable, A16, I16, I8,
+        M16, R16, R8,
+    },
+    diagnostic::{Diagnostic, Diagnostics},
+};
 
 trait Compile {
     fn compile(&self) -> Vec<u8>;
 }
 
+impl Compile for Arg1<R8> {
+    fn compile(&self) -> Vec<u8> {
+        self.arg1.compile()
+    }
+}
+
+impl Compile for Arg1<R16> {
+    fn compile(&self) -> Vec<u8> {
+        self.arg1.compile()
+    }
+}
+
 impl Compile for Arg2<R8, R8> {
     fn compile(&self) -> Vec<u8> {
         let arg = (self.arg2.compile()[0] << 4) | self.arg1.compile()[0];
@@ -35,7 +53,7 @@ impl Compile for Arg2<R16, I16> {
     }
 }
 
-impl Compile for Arg2<A16, R16> {
+impl Compile for Arg2<M16, R16> {
     fn compile(&self) -> Vec<u8> {
         let arg = self.arg1.compile();
         let arg2 = self.arg2.compile()[0];
@@ -43,7 +61,7 @@ impl Compile for Arg2<A16, R16> {
     }
 }
 
-impl Compile for Arg2<A16, I16> {
+impl Compile for Arg2<M16, I16> {
     fn compile(&self) -> Vec<u8> {
         let arg = self.arg1.compile();
         let arg2 = self.arg2.compile();
@@ -51,7 +69,7 @@ impl Compile for Arg2<A16, I16> {
     }
 }
 
-impl Compile for Arg2<A16, R8> {
+impl Compile for Arg2<M16, R8> {
     fn compile(&self) -> Vec<u8> {
         let arg = self.arg1.compile();
         let arg2 = self.arg2.compile()[0];
@@ -59,7 +77,7 @@ impl Compile for Arg2<A16, R8> {
     }
 }
 
-impl Compile for Arg2<A16, I8> {
+impl Compile for Arg2<M16, I8> {
     fn compile(&self) -> Vec<u8> {
         let arg = self.arg1.compile();
         let arg2 = self.arg2.compile()[0];
@@ -88,6 +106,9 @@ impl Compile for R16 {
         let val: u8 = match self.register {
             R16Regs::L0 => 0b1001,
             R16Regs::L1 => 0b1010,
+            R16Regs::Vp => 0b1000,
+            R16Regs::Cr => 0b1101,
+            R16Regs::Sp => 0b1110,
         };
         vec![val]
     }
@@ -111,7 +132,25 @@ impl Compile for A16 {
     }
 }
 
-#[allow(dead_code)]
+/// Indirect addresses are encoded with the mode in the high nibble and
+/// the base register in the low nibble, followed by the index register or immediate
+impl Compile for M16 {
+    fn compile(&self) -> Vec<u8> {
+        match self {
+            M16::Absolute(addr) => addr.compile(),
+            M16::Indirect { base, offset } => {
+                let (mode, arg) = match *offset {
+                    Offset::None => (0b0000, 0),
+                    Offset::Register(index) => (0b0001, index),
+                    Offset::Add(imm) => (0b0010, imm),
+                    Offset::Sub(imm) => (0b0011, imm),
+                };
+                vec![(mode << 4) | base, arg]
+            }
+        }
+    }
+}
+
 enum ALUType {
     Add,
     Subtract,
@@ -121,6 +160,12 @@ enum ALUType {
     Not,
     Equality,
     IncrDecr,
+    Multiply,
+    Divide,
+    Modulo,
+    ShiftLeft,
+    ShiftRight,
+    ShiftRightArithmetic,
 }
 
 /// If `opcode[2:4] != 0b111`:
@@ -132,7 +177,6 @@ enum ALUType {
 /// `opcode[5]` - Function
 ///  * `0b0` - Increment
 ///  * `0b1` - Decrement
-#[allow(dead_code)]
 enum ALUSrc {
     Register,
     Immidiate,
@@ -140,7 +184,6 @@ enum ALUSrc {
     Decrement,
 }
 
-#[allow(dead_code)]
 enum LoadStoreType {
     Load,
     Store,
@@ -152,6 +195,16 @@ enum BranchCall {
     Jump,
     BranchEq,
     BranchNe,
+    BranchZero,
+    BranchNotZero,
+    BranchCarry,
+    BranchNotCarry,
+    BranchOverflow,
+    BranchNotOverflow,
+    BranchSign,
+    BranchNotSign,
+    BranchError,
+    BranchNotError,
     BranchGt,
     BranchLt,
     Call,
@@ -169,6 +222,13 @@ fn compile_alu_equality(tt: ALUType, source: ALUSrc, is_16b: bool, noop: bool) -
         ALUType::Not => 0b00_101_0_0_0,
         ALUType::Equality => 0b00_110_0_0_0,
         ALUType::IncrDecr => 0b00_111_0_0_0,
+        // The noop bit selects the extended operations
+        ALUType::Multiply => 0b00_000_0_0_1,
+        ALUType::Divide => 0b00_001_0_0_1,
+        ALUType::Modulo => 0b00_010_0_0_1,
+        ALUType::ShiftLeft => 0b00_011_0_0_1,
+        ALUType::ShiftRight => 0b00_100_0_0_1,
+        ALUType::ShiftRightArithmetic => 0b00_101_0_0_1,
     };
 
     let mut op = match source {
@@ -187,11 +247,18 @@ fn compile_alu_equality(tt: ALUType, source: ALUSrc, is_16b: bool, noop: bool) -
     op
 }
 
+/// ALU opcode followed by the compiled arguments
+fn compile_alu<T: Compile>(tt: ALUType, source: ALUSrc, is_16b: bool, args: &T) -> Vec<u8> {
+    let mut args = args.compile();
+    args.insert(0, compile_alu_equality(tt, source, is_16b, false));
+    args
+}
+
 fn compile_branch_call<'a>(
     tt: BranchCall,
-    label: &'a str,
-    label_instrs: &mut Vec<(&'a str, usize, bool)>,
-    labels: &mut [(&'a str, u16)],
+    label: &'a Ident,
+    label_instrs: &mut Vec<(&'a Ident, usize, bool)>,
+    labels: &mut [(&'a Ident, u16)],
     instr_len: usize,
 ) -> Vec<u8> {
     #[allow(clippy::unusual_byte_groupings)]
@@ -199,6 +266,16 @@ fn compile_branch_call<'a>(
         BranchCall::Jump => 0b11_000_000,
         BranchCall::BranchEq => 0b11_001_000,
         BranchCall::BranchNe => 0b11_010_000,
+        BranchCall::BranchZero => 0b11_001_001,
+        BranchCall::BranchNotZero => 0b11_010_001,
+        BranchCall::BranchCarry => 0b11_001_010,
+        BranchCall::BranchNotCarry => 0b11_010_010,
+        BranchCall::BranchOverflow => 0b11_001_011,
+        BranchCall::BranchNotOverflow => 0b11_010_011,
+        BranchCall::BranchSign => 0b11_001_100,
+        BranchCall::BranchNotSign => 0b11_010_100,
+        BranchCall::BranchError => 0b11_001_101,
+        BranchCall::BranchNotError => 0b11_010_101,
         BranchCall::BranchGt => 0b11_011_000,
         BranchCall::BranchLt => 0b11_100_000,
         BranchCall::Call => 0b11_101_000,
@@ -207,7 +284,7 @@ fn compile_branch_call<'a>(
 
     let mut args = vec![op, 0, 0];
 
-    if let Some(addr) = labels.iter().find(|(l, _)| l == &label) {
+    if let Some(addr) = labels.iter().find(|(l, _)| l.name == label.name) {
         let add_le = addr.1.to_le_bytes();
         args[1] = add_le[0];
         args[2] = add_le[1];
@@ -223,7 +300,7 @@ fn compile_load_store(
     is_memtarget: bool,
     is_immiddiate: bool,
     is_16b: bool,
-    is_cr: bool,
+    is_indirect: bool,
 ) -> u8 {
     #[allow(clippy::unusual_byte_groupings)]
     let mut op = match tt {
@@ -244,34 +321,56 @@ fn compile_load_store(
         op |= 0b10;
     }
 
-    if is_cr {
+    if is_indirect {
         op |= 0b1;
     }
 
     op
 }
 
-fn compile_variables(vars: &Vec<Variable>) -> Storage {
+fn compile_variables(vars: &Vec<Variable>, diagnostics: &mut Diagnostics) -> Storage {
     // total size in bytes!
-    let mut total_size = 0;
-    let mut offset = 0;
+    let mut total_size: u16 = 0;
+    let mut offset: u16 = 0;
     let mut items: Vec<StorageItem> = Vec::new();
     for var in vars {
-        let mut size = var.size;
+        if let Some(first) = vars.iter().find(|v| v.name == var.name) {
+            if !std::ptr::eq(first, var) {
+                let msg = format!("Duplicate variable '{}' found", var.name);
+                let note = format!("First defined on line {}", first.span.line);
+                diagnostics.push(Diagnostic::error(var.span, msg).with_note(note));
+            }
+        }
+
+        // The highest bit of the size is used as the init_data flag
+        if var.size > 0x7fff {
+            let msg = format!("Variable '{}' is too large", var.name);
+            let note = format!("Variables can be at most {} bytes", 0x7fff);
+            diagnostics.push(Diagnostic::error(var.span, msg).with_note(note));
+
+            // Keep the items at the same indexes as the variables
+            items.push(StorageItem {
+                size: 0,
+                offset,
+                init_data: None,
+            });
+            continue;
+        }
+
+        let size = var.size;
         let init_data = if let Some(data) = &var.bytes {
             if data.len() != var.size as usize {
-                panic!(
+                let msg = format!(
                     "Variable '{}' initial value's length expected to be {}, was {}",
                     var.name,
                     var.size,
                     data.len()
                 );
+                diagnostics.push(Diagnostic::error(var.span, msg));
             }
 
-            // Set the higest bit to signal initialised data
-            size |= 0x8000;
             // Save space for the variable size
-            total_size += var.size;
+            total_size = total_size.saturating_add(var.size);
             Some(data)
         } else {
             None
@@ -284,76 +383,122 @@ fn compile_variables(vars: &Vec<Variable>) -> Storage {
         });
 
         // 4 bytes for the two u16
-        total_size += 4;
-        offset += var.size;
+        total_size = total_size.saturating_add(4);
+        // Variables can't reach the device registers after the variable segment
+        offset = match offset.checked_add(var.size) {
+            Some(end) if end <= memory::VARIABLE_SIZE => end,
+            _ => {
+                let msg = format!("Variable '{}' doesn't fit into the memory", var.name);
+                diagnostics.push(Diagnostic::error(var.span, msg));
+                offset
+            }
+        };
+    }
+
+    if total_size == u16::MAX {
+        let msg = "Variable section is too large to be saved";
+        diagnostics.push(Diagnostic::file_error(msg));
     }
 
     Storage { total_size, items }
 }
 
-fn variable_offset(name: &str, ast: &ASTTree, storage: &Storage) -> u16 {
+fn variable_offset(name: &Ident, ast: &ASTTree, storage: &Storage) -> Result<u16, Diagnostic> {
     // Variable and storage items are handeled in order so they have the same indexes
     let idx = ast
         .variables
         .iter()
-        .position(|v| v.name == name)
-        .unwrap_or_else(|| panic!("Variable '{name}' is not defined"));
+        .position(|v| v.name == name.name)
+        .ok_or_else(|| {
+            let msg = format!("Variable '{}' is not defined", name.name);
+            Diagnostic::error(name.span, msg)
+        })?;
 
-    storage.items[idx].offset
+    Ok(storage.items[idx].offset)
 }
 
-pub fn compile_ast(ast: ASTTree) -> SmolFile {
-    let storage = compile_variables(&ast.variables);
+/// Compile the tree into an object file and the symbols of its labels.
+/// Returns None if any errors were added to `diagnostics`.
+pub fn compile_ast(ast: ASTTree, diagnostics: &mut Diagnostics) -> Option<(SmolFile, Vec<Symbol>)> {
+    let storage = compile_variables(&ast.variables, diagnostics);
 
     // When coming acorss a labe instruction, check if the label already exists
     // if it does, get the address and compile it
     // if it doesn't, save the label instr here and mutate when you find the label
-    let mut labels: Vec<(&str, u16)> = Vec::new();
-    let mut label_instrs: Vec<(&str, usize, bool)> = Vec::new();
+    let mut labels: Vec<(&Ident, u16)> = Vec::new();
+    let mut label_instrs: Vec<(&Ident, usize, bool)> = Vec::new();
+    let mut used_variables: Vec<&str> = Vec::new();
 
     let mut instructions: Vec<u8> = Vec::new();
 
+    use ALUSrc::*;
+    use ALUType::*;
     for instr in &ast.instructions {
         let bytes = match instr {
-            Instruction::Add(instr) => {
-                let mut args = instr.inner().compile();
-                let op = compile_alu_equality(ALUType::Add, ALUSrc::Register, false, false);
-                args.insert(0, op);
-                args
-            }
-            Instruction::AddI(instr) => {
-                let mut args = instr.inner().compile();
-                let op = compile_alu_equality(ALUType::Add, ALUSrc::Immidiate, false, false);
-                args.insert(0, op);
-                args
-            }
-            Instruction::EqR(instr) => {
-                let mut args = instr.inner().compile();
-                let op = compile_alu_equality(ALUType::Equality, ALUSrc::Register, false, false);
-                args.insert(0, op);
-                args
-            }
-            Instruction::EqI(instr) => {
-                let mut args = instr.inner().compile();
-                let op = compile_alu_equality(ALUType::Equality, ALUSrc::Immidiate, false, false);
-                args.insert(0, op);
-                args
-            }
-            Instruction::EqRL(instr) => {
-                let mut args = instr.inner().compile();
-                let op = compile_alu_equality(ALUType::Equality, ALUSrc::Register, true, false);
-                args.insert(0, op);
-                args
-            }
-            Instruction::EqIL(instr) => {
-                let mut args = instr.inner().compile();
-                let op = compile_alu_equality(ALUType::Equality, ALUSrc::Immidiate, true, false);
-                args.insert(0, op);
-                args
-            }
+            Instruction::Add(i) => compile_alu(Add, Register, false, i.inner()),
+            Instruction::AddI(i) => compile_alu(Add, Immidiate, false, i.inner()),
+            Instruction::AddL(i) => compile_alu(Add, Register, true, i.inner()),
+            Instruction::AddIL(i) => compile_alu(Add, Immidiate, true, i.inner()),
+            Instruction::Sub(i) => compile_alu(Subtract, Register, false, i.inner()),
+            Instruction::SubI(i) => compile_alu(Subtract, Immidiate, false, i.inner()),
+            Instruction::SubL(i) => compile_alu(Subtract, Register, true, i.inner()),
+            Instruction::SubIL(i) => compile_alu(Subtract, Immidiate, true, i.inner()),
+            Instruction::And(i) => compile_alu(And, Register, false, i.inner()),
+            Instruction::AndI(i) => compile_alu(And, Immidiate, false, i.inner()),
+            Instruction::AndL(i) => compile_alu(And, Register, true, i.inner()),
+            Instruction::AndIL(i) => compile_alu(And, Immidiate, true, i.inner()),
+            Instruction::Or(i) => compile_alu(Or, Register, false, i.inner()),
+            Instruction::OrI(i) => compile_alu(Or, Immidiate, false, i.inner()),
+            Instruction::OrL(i) => compile_alu(Or, Register, true, i.inner()),
+            Instruction::OrIL(i) => compile_alu(Or, Immidiate, true, i.inner()),
+            Instruction::Xor(i) => compile_alu(Xor, Register, false, i.inner()),
+            Instruction::XorI(i) => compile_alu(Xor, Immidiate, false, i.inner()),
+            Instruction::XorL(i) => compile_alu(Xor, Register, true, i.inner()),
+            Instruction::XorIL(i) => compile_alu(Xor, Immidiate, true, i.inner()),
+            Instruction::Not(i) => compile_alu(Not, Register, false, i.inner()),
+            Instruction::NotL(i) => compile_alu(Not, Register, true, i.inner()),
+            Instruction::Inc(i) => compile_alu(IncrDecr, Incerement, false, i.inner()),
+            Instruction::IncL(i) => compile_alu(IncrDecr, Incerement, true, i.inner()),
+            Instruction::Dec(i) => compile_alu(IncrDecr, Decrement, false, i.inner()),
+            Instruction::DecL(i) => compile_alu(IncrDecr, Decrement, true, i.inner()),
+            Instruction::EqR(i) => compile_alu(Equality, Register, false, i.inner()),
+            Instruction::EqI(i) => compile_alu(Equality, Immidiate, false, i.inner()),
+            Instruction::EqRL(i) => compile_alu(Equality, Register, true, i.inner()),
+            Instruction::EqIL(i) => compile_alu(Equality, Immidiate, true, i.inner()),
+            Instruction::Mul(i) => compile_alu(Multiply, Register, false, i.inner()),
+            Instruction::MulI(i) => compile_alu(Multiply, Immidiate, false, i.inner()),
+            Instruction::MulL(i) => compile_alu(Multiply, Register, true, i.inner()),
+            Instruction::MulIL(i) => compile_alu(Multiply, Immidiate, true, i.inner()),
+            Instruction::Div(i) => compile_alu(Divide, Register, false, i.inner()),
+            Instruction::DivI(i) => compile_alu(Divide, Immidiate, false, i.inner()),
+            Instruction::DivL(i) => compile_alu(Divide, Register, true, i.inner()),
+            Instruction::DivIL(i) => compile_alu(Divide, Immidiate, true, i.inner()),
+            Instruction::Mod(i) => compile_alu(Modulo, Register, false, i.inner()),
+            Instruction::ModI(i) => compile_alu(Modulo, Immidiate, false, i.inner()),
+            Instruction::ModL(i) => compile_alu(Modulo, Register, true, i.inner()),
+            Instruction::ModIL(i) => compile_alu(Modulo, Immidiate, true, i.inner()),
+            Instruction::Shl(i) => compile_alu(ShiftLeft, Register, false, i.inner()),
+            Instruction::ShlI(i) => compile_alu(ShiftLeft, Immidiate, false, i.inner()),
+            Instruction::ShlL(i) => compile_alu(ShiftLeft, Register, true, i.inner()),
+            Instruction::ShlIL(i) => compile_alu(ShiftLeft, Immidiate, true, i.inner()),
+            Instruction::Shr(i) => compile_alu(ShiftRight, Register, false, i.inner()),
+            Instruction::ShrI(i) => compile_alu(ShiftRight, Immidiate, false, i.inner()),
+            Instruction::ShrL(i) => compile_alu(ShiftRight, Register, true, i.inner()),
+            Instruction::ShrIL(i) => compile_alu(ShiftRight, Immidiate, true, i.inner()),
+            Instruction::Sar(i) => compile_alu(ShiftRightArithmetic, Register, false, i.inner()),
+            Instruction::SarI(i) => compile_alu(ShiftRightArithmetic, Immidiate, false, i.inner()),
+            Instruction::SarL(i) => compile_alu(ShiftRightArithmetic, Register, true, i.inner()),
+            Instruction::SarIL(i) => compile_alu(ShiftRightArithmetic, Immidiate, true, i.inner()),
             Instruction::Sv(name) => {
                 let name = name.inner();
-                let offset = variable_offset(name, &ast, &storage);
+                used_variables.push(&name.name);
+                let offset = match variable_offset(name, &ast, &storage) {
+                    Ok(offset) => offset,
+                    Err(diag) => {
+                        diagnostics.push(diag);
+                        0
+                    }
+                };
                 let [li, mi] = offset.to_le_bytes();
                 // Stack load variable immediate 16 bit
                 [0b10101100, li, mi].into()
@@ -391,41 +536,96 @@ pub fn compile_ast(ast: ASTTree) -> SmolFile {
                 args
             }
             Instruction::Stm(instr) => {
-                let mut args = instr.inner().compile();
-                let op = compile_load_store(LoadStoreType::Store, true, true, false, false);
+                let args = instr.inner();
+                let is_indirect = args.arg1.is_indirect();
+                let mut args = args.compile();
+                let op = compile_load_store(LoadStoreType::Store, true, true, false, is_indirect);
                 args.insert(0, op);
                 args
             }
             Instruction::StmL(instr) => {
-                let mut args = instr.inner().compile();
-                let op = compile_load_store(LoadStoreType::Store, true, true, true, false);
+                let args = instr.inner();
+                let is_indirect = args.arg1.is_indirect();
+                let mut args = args.compile();
+                let op = compile_load_store(LoadStoreType::Store, true, true, true, is_indirect);
                 args.insert(0, op);
                 args
             }
             Instruction::Str(instr) => {
-                let mut args = instr.inner().compile();
-                let op = compile_load_store(LoadStoreType::Store, true, false, false, false);
+                let args = instr.inner();
+                let is_indirect = args.arg1.is_indirect();
+                let mut args = args.compile();
+                let op = compile_load_store(LoadStoreType::Store, true, false, false, is_indirect);
                 args.insert(0, op);
                 args
             }
             Instruction::StrL(instr) => {
-                let mut args = instr.inner().compile();
-                let op = compile_load_store(LoadStoreType::Store, true, false, true, false);
+                let args = instr.inner();
+                let is_indirect = args.arg1.is_indirect();
+                let mut args = args.compile();
+                let op = compile_load_store(LoadStoreType::Store, true, false, true, is_indirect);
                 args.insert(0, op);
                 args
             }
             Instruction::Ldm(instr) => {
-                let mut args = instr.inner().compile();
-                let op = compile_load_store(LoadStoreType::Load, true, false, false, false);
+                let args = instr.inner();
+                let is_indirect = args.arg1.is_indirect();
+                let mut args = args.compile();
+                let op = compile_load_store(LoadStoreType::Load, true, false, false, is_indirect);
                 args.insert(0, op);
                 args
             }
             Instruction::LdmL(instr) => {
+                let args = instr.inner();
+                let is_indirect = args.arg1.is_indirect();
+                let mut args = args.compile();
+                let op = compile_load_store(LoadStoreType::Load, true, false, true, is_indirect);
+                args.insert(0, op);
+                args
+            }
+            Instruction::Swp(instr) => {
                 let mut args = instr.inner().compile();
-                let op = compile_load_store(LoadStoreType::Load, true, false, true, false);
+                let op = compile_load_store(LoadStoreType::Swap, false, false, false, false);
+                args.insert(0, op);
+                args
+            }
+            Instruction::SwpL(instr) => {
+                let mut args = instr.inner().compile();
+                let op = compile_load_store(LoadStoreType::Swap, false, false, true, false);
+                args.insert(0, op);
+                args
+            }
+            Instruction::SwpM(instr) => {
+                let args = instr.inner();
+                let is_indirect = args.arg1.is_indirect();
+                let mut args = args.compile();
+                let op = compile_load_store(LoadStoreType::Swap, true, false, false, is_indirect);
                 args.insert(0, op);
                 args
             }
+            Instruction::SwpML(instr) => {
+                let args = instr.inner();
+                let is_indirect = args.arg1.is_indirect();
+                let mut args = args.compile();
+                let op = compile_load_store(LoadStoreType::Swap, true, false, true, is_indirect);
+                args.insert(0, op);
+                args
+            }
+            Instruction::Jmp(instr) => {
+                let label = instr.inner();
+                compile_branch_call(
+                    BranchCall::Jump,
+                    label,
+                    &mut label_instrs,
+                    &mut labels,
+                    instructions.len(),
+                )
+            }
+            Instruction::JmpR(instr) => {
+                let mut args = instr.inner().compile();
+                args.insert(0, 0b11_000_001);
+                args
+            }
             Instruction::Be(instr) => {
                 let label = instr.inner();
                 compile_branch_call(
@@ -446,6 +646,106 @@ pub fn compile_ast(ast: ASTTree) -> SmolFile {
                     instructions.len(),
                 )
             }
+            Instruction::Bz(instr) => {
+                let label = instr.inner();
+                compile_branch_call(
+                    BranchCall::BranchZero,
+                    label,
+                    &mut label_instrs,
+                    &mut labels,
+                    instructions.len(),
+                )
+            }
+            Instruction::Bnz(instr) => {
+                let label = instr.inner();
+                compile_branch_call(
+                    BranchCall::BranchNotZero,
+                    label,
+                    &mut label_instrs,
+                    &mut labels,
+                    instructions.len(),
+                )
+            }
+            Instruction::Bc(instr) => {
+                let label = instr.inner();
+                compile_branch_call(
+                    BranchCall::BranchCarry,
+                    label,
+                    &mut label_instrs,
+                    &mut labels,
+                    instructions.len(),
+                )
+            }
+            Instruction::Bnc(instr) => {
+                let label = instr.inner();
+                compile_branch_call(
+                    BranchCall::BranchNotCarry,
+                    label,
+                    &mut label_instrs,
+                    &mut labels,
+                    instructions.len(),
+                )
+            }
+            Instruction::Bo(instr) => {
+                let label = instr.inner();
+                compile_branch_call(
+                    BranchCall::BranchOverflow,
+                    label,
+                    &mut label_instrs,
+                    &mut labels,
+                    instructions.len(),
+                )
+            }
+            Instruction::Bno(instr) => {
+                let label = instr.inner();
+                compile_branch_call(
+                    BranchCall::BranchNotOverflow,
+                    label,
+                    &mut label_instrs,
+                    &mut labels,
+                    instructions.len(),
+                )
+            }
+            Instruction::Bs(instr) => {
+                let label = instr.inner();
+                compile_branch_call(
+                    BranchCall::BranchSign,
+                    label,
+                    &mut label_instrs,
+                    &mut labels,
+                    instructions.len(),
+                )
+            }
+            Instruction::Bns(instr) => {
+                let label = instr.inner();
+                compile_branch_call(
+                    BranchCall::BranchNotSign,
+                    label,
+                    &mut label_instrs,
+                    &mut labels,
+                    instructions.len(),
+                )
+            }
+            Instruction::Berr(instr) => {
+                let label = instr.inner();
+                compile_branch_call(
+                    BranchCall::BranchError,
+                    label,
+                    &mut label_instrs,
+                    &mut labels,
+                    instructions.len(),
+                )
+            }
+            Instruction::Bnerr(instr) => {
+                let label = instr.inner();
+                compile_branch_call(
+                    BranchCall::BranchNotError,
+                    label,
+                    &mut label_instrs,
+                    &mut labels,
+                    instructions.len(),
+                )
+            }
             Instruction::Bgt(instr) => {
                 let label = instr.inner();
                 compile_branch_call(
@@ -476,18 +776,27 @@ pub fn compile_ast(ast: ASTTree) -> SmolFile {
                     instructions.len(),
                 )
             }
+            Instruction::CallR(instr) => {
+                let mut args = instr.inner().compile();
+                args.insert(0, 0b11_101_001);
+                args
+            }
             Instruction::Ret(_) => [0b11_110_000].into(),
+            Instruction::Reti(_) => [0b11_111_000].into(),
+            Instruction::Ei(_) => [0b11_111_001].into(),
+            Instruction::Di(_) => [0b11_111_010].into(),
             Instruction::Label(label) => {
-                let exists = labels.iter().any(|(lab, _)| lab == label);
-                if exists {
-                    // TODO: AST should handle this
-                    panic!("Duplicate label '{label}' found");
+                if let Some((first, _)) = labels.iter().find(|(lab, _)| lab.name == label.name) {
+                    let msg = format!("Duplicate label '{}' found", label.name);
+                    let note = format!("First defined on line {}", first.span.line);
+                    diagnostics.push(Diagnostic::error(label.span, msg).with_note(note));
+                    continue;
                 }
 
                 let addr = instructions.len() as u16;
                 let add_le = addr.to_le_bytes();
                 label_instrs.iter_mut().for_each(|(lab, addr, init)| {
-                    if lab == label {
+                    if lab.name == label.name {
                         let bytes = &mut instructions;
                         bytes[*addr] = add_le[0];
                         bytes[*addr + 1] = add_le[1];
@@ -504,21 +813,48 @@ pub fn compile_ast(ast: ASTTree) -> SmolFile {
         instructions.extend(bytes.iter());
     }
 
-    for label in label_instrs {
-        if !label.2 {
-            panic!("Label for '{}' was not found", label.0);
+    for (label, _, found) in label_instrs {
+        if !found {
+            let msg = format!("Label '{}' was not found", label.name);
+            diagnostics.push(Diagnostic::error(label.span, msg));
         }
     }
 
-    let main_start = if let Some((_, addr)) = labels.iter().find(|(name, _)| *name == "main") {
-        *addr
-    } else {
-        panic!("main label was not found");
-    };
+    for var in &ast.variables {
+        if !used_variables.contains(&var.name.as_str()) {
+            let msg = format!("Variable '{}' is never used", var.name);
+            diagnostics.push(Diagnostic::warning(var.span, msg));
+        }
+    }
 
-    SmolFile {
+    let main_start = labels
+        .iter()
+        .find(|(label, _)| label.name == "main")
+        .map(|(_, addr)| *addr);
+    if main_start.is_none() {
+        let msg = "main label was not found";
+        diagnostics.push(Diagnostic::file_error(msg).with_note("Add a 'main:' label"));
+    }
+
+    if diagnostics.has_errors() {
+        return None;
+    }
+
+    let main_start = main_start?;
+
+    let symbols = labels
+        .iter()
+        .map(|(label, address)| Symbol {
+            name: label.name.clone(),
+            address: *address,
+        })
+        .collect();
+
+    let file = SmolFile {
         storage,
         main_start,
         instructions,
-    }
+    };
+
+    Some((file, symbols))
 }
@@ -0,0 +1,150 @@
+use std::fmt::Write;
+
+/// Location of a piece of source code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    /// 1-based line in the source file
+    pub line: usize,
+    /// 1-based column of the first character
+    pub column: usize,
+    /// Number of characters to underline
+    pub len: usize,
+}
+
+impl Span {
+    pub fn new(line: usize, column: usize, len: usize) -> Self {
+        Self { line, column, len }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// None if the diagnostic is about the whole file
+    pub span: Option<Span>,
+    pub note: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn error(span: Span, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.into(),
+            span: Some(span),
+            note: None,
+        }
+    }
+
+    pub fn warning(span: Span, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message: message.into(),
+            span: Some(span),
+            note: None,
+        }
+    }
+
+    /// Error without a location in the source
+    pub fn file_error(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.into(),
+            span: None,
+            note: None,
+        }
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.note = Some(note.into());
+        self
+    }
+
+    /// Render the diagnostic in the style of rustc:
+    ///
+    /// ```text
+    /// error: Expected r0-7, received r9
+    ///  --> main.asm:3:9
+    ///   |
+    /// 3 |     add r9 r1
+    ///   |         ^^
+    /// ```
+    pub fn render(&self, source: &str, file_name: &str) -> String {
+        let mut out = String::new();
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        let _ = writeln!(out, "{severity}: {}", self.message);
+
+        let Some(span) = self.span else {
+            let _ = writeln!(out, " --> {file_name}");
+            if let Some(note) = &self.note {
+                let _ = writeln!(out, "  = note: {note}");
+            }
+            return out;
+        };
+
+        let line_no = span.line.to_string();
+        let pad = " ".repeat(line_no.len());
+        let line = source.lines().nth(span.line - 1).unwrap_or("");
+        // Tabs would break the alignment of the underline
+        let line = line.replace('\t', " ");
+
+        let _ = writeln!(out, "{pad}--> {file_name}:{}:{}", span.line, span.column);
+        let _ = writeln!(out, "{pad} |");
+        let _ = writeln!(out, "{line_no} | {line}");
+        let _ = writeln!(
+            out,
+            "{pad} | {}{}",
+            " ".repeat(span.column - 1),
+            "^".repeat(span.len.max(1))
+        );
+        if let Some(note) = &self.note {
+            let _ = writeln!(out, "{pad} = note: {note}");
+        }
+
+        out
+    }
+}
+
+/// All of the diagnostics collected while assembling a file
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Diagnostics {
+    items: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    pub fn push(&mut self, diagnostic: Diagnostic) {
+        self.items.push(diagnostic);
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.items
+            .iter()
+            .any(|diag| diag.severity == Severity::Error)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.items.iter()
+    }
+
+    /// Render all of the diagnostics, separated by empty lines
+    pub fn render(&self, source: &str, file_name: &str) -> String {
+        self.items
+            .iter()
+            .map(|diag| diag.render(source, file_name))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
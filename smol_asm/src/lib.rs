@@ -0,0 +1,51 @@
+//! Assembler for the smol VM.
+//!
+//! ```
+//! let source = "main:\n    sti r0 1\n    addi r0 2\n";
+//! let file = smol_asm::assemble(source, &smol_asm::Options::default()).unwrap();
+//! assert_eq!(file.main_start, 0);
+//! ```
+
+use smol_file::{SmolFile, Symbol};
+
+mod ast;
+mod compiler;
+pub mod diagnostic;
+
+pub use diagnostic::{Diagnostic, Diagnostics, Severity, Span};
+
+/// Settings for assembling a source file
+#[derive(Debug, Default, Clone)]
+pub struct Options {
+    /// Fail the assembly if there are any warnings
+    pub warnings_as_errors: bool,
+}
+
+/// Assemble the source into an object file.
+/// Warnings are dropped if the assembly succeeds, use [compile] to keep them.
+pub fn assemble(source: &str, options: &Options) -> Result<SmolFile, Diagnostics> {
+    let mut diagnostics = Diagnostics::default();
+    match compile(source, options, &mut diagnostics) {
+        Some((file, _)) => Ok(file),
+        None => Err(diagnostics),
+    }
+}
+
+/// Assemble the source into an object file and the symbols of its labels.
+/// Errors and warnings are added to `diagnostics`, returns None on errors.
+pub fn compile(
+    source: &str,
+    options: &Options,
+    diagnostics: &mut Diagnostics,
+) -> Option<(SmolFile, Vec<Symbol>)> {
+    let tree = ast::parse_source(source, diagnostics);
+    if diagnostics.has_errors() {
+        return None;
+    }
+
+    let compiled = compiler::compile_ast(tree, diagnostics)?;
+    if options.warnings_as_errors && !diagnostics.is_empty() {
+        return None;
+    }
+    Some(compiled)
+}
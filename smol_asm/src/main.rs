@@ -1,17 +1,40 @@
 use std::{fs, process::exit};
 
-mod ast;
-mod compiler;
+use smol_asm::{Diagnostics, Options};
 
 fn main() {
     let args: Vec<String> = std::env::args().collect();
     if args.len() < 2 {
         println!("Give file as an argument");
+        println!("Usage: {} <file.asm> [--deny-warnings]", args[0]);
         exit(1);
     }
 
+    let options = Options {
+        warnings_as_errors: args.iter().skip(2).any(|arg| arg == "--deny-warnings"),
+    };
+
     let file_contents = fs::read_to_string(&args[1]).unwrap();
-    let tree = ast::parse_source(&file_contents).unwrap();
-    let binary = compiler::compile_ast(tree);
-    binary.save(&format!("{}.obj", &args[1]));
+    let mut diagnostics = Diagnostics::default();
+    let compiled = smol_asm::compile(&file_contents, &options, &mut diagnostics);
+
+    if !diagnostics.is_empty() {
+        eprint!("{}", diagnostics.render(&file_contents, &args[1]));
+    }
+
+    let Some((binary, symbols)) = compiled else {
+        exit(1);
+    };
+
+    let obj_path = format!("{}.obj", &args[1]);
+    if let Err(err) = binary.save(&obj_path) {
+        eprintln!("Error: {obj_path}: {err}");
+        exit(1);
+    }
+
+    let sym_path = format!("{}.sym", &args[1]);
+    if let Err(err) = smol_file::save_symbols(&sym_path, &symbols) {
+        eprintln!("Error: {sym_path}: {err}");
+        exit(1);
+    }
 }
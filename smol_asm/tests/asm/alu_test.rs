@@ -0,0 +1,62 @@
+use smol_asm::{assemble, Options};
+
+fn encode(instruction: &str) -> Vec<u8> {
+    let source = format!("main:\n    {instruction}\n");
+    assemble(&source, &Options::default()).unwrap().instructions
+}
+
+/// Register mnemonic, immediate mnemonic and the opcode of the register form
+const BINARY: [(&str, &str, u8); 6] = [
+    ("add", "addi", 0b00_000_0_0_0),
+    ("sub", "subi", 0b00_001_0_0_0),
+    ("and", "andi", 0b00_010_0_0_0),
+    ("or", "ori", 0b00_011_0_0_0),
+    ("xor", "xori", 0b00_100_0_0_0),
+    ("eqr", "eqi", 0b00_110_0_0_0),
+];
+
+#[test]
+pub fn it_encodes_register_alu_instructions() {
+    for (reg, _, opcode) in BINARY {
+        // Target r2 in the low bits, source r1 in the high bits
+        assert_eq!(encode(&format!("{reg} r2 r1")), [opcode, 0b0001_0010]);
+        assert_eq!(
+            encode(&format!("{reg}l l0 l1")),
+            [opcode | 0b10, 0b1010_1001],
+            "{reg}l"
+        );
+    }
+}
+
+#[test]
+pub fn it_encodes_immediate_alu_instructions() {
+    for (_, imm, opcode) in BINARY {
+        assert_eq!(
+            encode(&format!("{imm} r2 7")),
+            [opcode | 0b100, 0b0010, 7],
+            "{imm}"
+        );
+        assert_eq!(
+            encode(&format!("{imm}l l0 4660")),
+            [opcode | 0b110, 0b1001, 0x34, 0x12],
+            "{imm}l"
+        );
+    }
+}
+
+#[test]
+pub fn it_encodes_unary_alu_instructions() {
+    let cases: [(&str, Vec<u8>); 6] = [
+        ("not r3", vec![0b00_101_0_0_0, 0b0011]),
+        ("notl l1", vec![0b00_101_0_1_0, 0b1010]),
+        ("inc r3", vec![0b00_111_0_0_0, 0b0011]),
+        ("incl l1", vec![0b00_111_0_1_0, 0b1010]),
+        // Decrement sets the source bit
+        ("dec r3", vec![0b00_111_1_0_0, 0b0011]),
+        ("decl l1", vec![0b00_111_1_1_0, 0b1010]),
+    ];
+
+    for (instruction, bytes) in cases {
+        assert_eq!(encode(instruction), bytes, "{instruction}");
+    }
+}
@@ -0,0 +1,85 @@
+use smol_asm::{assemble, compile, Diagnostics, Options, Severity, Span};
+
+#[test]
+pub fn it_assembles_source() {
+    let source = "\
+---
+msg 2 \"hi\"
+---
+main:
+    sv msg
+    sti r0 1
+";
+    let file = assemble(source, &Options::default()).unwrap();
+
+    assert_eq!(file.main_start, 0);
+    assert_eq!(file.storage.items.len(), 1);
+    assert_eq!(file.storage.items[0].init_data, Some(b"hi".to_vec()));
+    assert_eq!(file.instructions[3..], [0b01_00_0_1_0_0, 0, 1]);
+}
+
+#[test]
+pub fn it_returns_errors() {
+    let diagnostics = assemble("main:\n    add r9 r1\n", &Options::default()).unwrap_err();
+    let errors: Vec<_> = diagnostics.iter().collect();
+
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].severity, Severity::Error);
+    assert_eq!(errors[0].span, Some(Span::new(2, 9, 2)));
+}
+
+#[test]
+pub fn it_keeps_warnings_and_symbols() {
+    let source = "---\nunused 1\n---\nmain:\n    sti r0 1\n";
+    let mut diagnostics = Diagnostics::default();
+    let (_, symbols) = compile(source, &Options::default(), &mut diagnostics).unwrap();
+
+    assert_eq!(symbols[0].name, "main");
+    assert_eq!(diagnostics.iter().count(), 1);
+    assert!(!diagnostics.has_errors());
+}
+
+#[test]
+pub fn it_denies_warnings() {
+    let source = "---\nunused 1\n---\nmain:\n    sti r0 1\n";
+    let options = Options {
+        warnings_as_errors: true,
+    };
+    let diagnostics = assemble(source, &options).unwrap_err();
+
+    assert_eq!(
+        diagnostics.iter().next().unwrap().severity,
+        Severity::Warning
+    );
+}
+
+#[test]
+pub fn it_assembles_special_16b_registers() {
+    let source = "main:\n    stil cr 5\n    stl l0 vp\n    addl sp l1\n";
+    let file = assemble(source, &Options::default()).unwrap();
+
+    assert_eq!(
+        file.instructions,
+        [
+            0b01_00_0_1_1_0,
+            0b1101,
+            5,
+            0,
+            0b01_00_0_0_1_0,
+            0b1000_1001,
+            0b00_000_0_1_0,
+            0b1010_1110,
+        ]
+    );
+}
+
+#[test]
+pub fn it_ignores_trailing_comments() {
+    let source = "main: # entry\n    addi r0 1 # comment\n    add r0 r1#no space\n";
+    let file = assemble(source, &Options::default()).unwrap();
+
+    assert_eq!(
+        file.instructions,
+        [0b00_000_1_0_0, 0b0000, 1, 0b00_000_0_0_0, 0b0001_0000]
+    );
+}
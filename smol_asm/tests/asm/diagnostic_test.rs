@@ -0,0 +1,73 @@
+use smol_asm::{assemble, Options, Severity, Span};
+
+#[test]
+pub fn it_collects_every_error() {
+    let source = "\
+main:
+    add r9 r1
+    sti r0 1
+    foo r1
+    sti r2 300
+";
+    let diagnostics = assemble(source, &Options::default()).unwrap_err();
+    let errors: Vec<_> = diagnostics
+        .iter()
+        .map(|diag| (diag.severity, diag.message.as_str(), diag.span))
+        .collect();
+
+    assert_eq!(
+        errors,
+        [
+            (
+                Severity::Error,
+                "Expected r0-7, received r9",
+                Some(Span::new(2, 9, 2))
+            ),
+            (
+                Severity::Error,
+                "Instruction 'foo' has not been implemented",
+                Some(Span::new(4, 5, 3))
+            ),
+            (
+                Severity::Error,
+                "Expected 0-255 value, got 300",
+                Some(Span::new(5, 12, 3))
+            ),
+        ]
+    );
+}
+
+#[test]
+pub fn it_collects_unresolved_names() {
+    let source = "main:\n    jmp missing\n    sv nothing\n";
+    let diagnostics = assemble(source, &Options::default()).unwrap_err();
+    let mut spans: Vec<_> = diagnostics.iter().map(|diag| diag.span).collect();
+    spans.sort_by_key(|span| span.map(|span| span.line));
+
+    assert_eq!(spans, [Some(Span::new(2, 9, 7)), Some(Span::new(3, 8, 7))]);
+}
+
+#[test]
+pub fn it_renders_every_error() {
+    let source = "main:\n    add r9 r1\n    foo r1\n";
+    let diagnostics = assemble(source, &Options::default()).unwrap_err();
+    let rendered = diagnostics.render(source, "main.asm");
+
+    assert!(rendered.contains(" --> main.asm:2:9\n  |\n2 |     add r9 r1\n  |         ^^\n"));
+    assert!(rendered.contains(" --> main.asm:3:5\n  |\n3 |     foo r1\n  |     ^^^\n"));
+}
+
+#[test]
+pub fn it_reports_oversized_variables() {
+    let source = "---\nbig 40000\nsmall 2\n---\nmain:\n    sv big\n    sv small\n";
+    let diagnostics = assemble(source, &Options::default()).unwrap_err();
+    let errors: Vec<_> = diagnostics.iter().collect();
+
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].message, "Variable 'big' is too large");
+    assert_eq!(errors[0].span, Some(Span::new(2, 1, 3)));
+    assert_eq!(
+        errors[0].note.as_deref(),
+        Some("Variables can be at most 32767 bytes")
+    );
+}
@@ -0,0 +1,3 @@
+mod alu_test;
+mod assemble_test;
+mod diagnostic_test;
@@ -1,56 +1,76 @@
-use std::fs;
+use std::{
+    fs,
+    io::{self, Read, Write},
+};
 
-#[derive(Debug)]
+pub mod memory;
+
+/// Flag in the size of a storage item telling that the initial data follows it
+const INIT_DATA_FLAG: u16 = 0x8000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct StorageItem {
     /// Size of the reserved space.
-    /// In the file format, the first byte tells if the storage is
-    /// reserved or not so the max storage is actually 15^2
+    /// In the file format, the highest bit tells if the storage is
+    /// initialised or not so the max storage is 0x7fff bytes
     pub size: u16,
-    /// Offset to the start of the storage space
+    /// Offset of the storage space from the start of the variable segment
     pub offset: u16,
     /// Possibly initialised data bytes.
     /// If data is initialised, the lenght needs to match `size`
     pub init_data: Option<Vec<u8>>,
 }
 
-#[derive(Debug)]
+impl StorageItem {
+    /// Addresses of the item relative to the variable segment
+    fn range(&self) -> std::ops::Range<usize> {
+        self.offset as usize..self.offset as usize + self.size as usize
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Storage {
-    /// Length of the storage bytes, defined by first 2 bytes
+    /// Length of the storage section in bytes
     pub total_size: u16,
     /// Storage items
     pub items: Vec<StorageItem>,
 }
 
 impl Storage {
-    fn load(data: &[u8]) -> Self {
-        let total_size = u16::from_le_bytes([data[0], data[1]]);
-        let mut bytes = &data[2..total_size as usize + 2];
+    /// Load `count` items from the storage section
+    fn load(count: u16, data: &[u8]) -> Result<Self, FileError> {
+        let total_size = data.len() as u16;
+        let mut bytes = data;
 
         let mut items: Vec<StorageItem> = Vec::new();
-        while !bytes.is_empty() {
+        for item in 0..count {
+            if bytes.len() < 4 {
+                return Err(FileError::TruncatedStorage { item });
+            }
+
             let size = u16::from_le_bytes([bytes[0], bytes[1]]);
             // We get the real size by removing the init_data flag
-            let rsize = size & 0x7fff;
-            // Add the variable stack address offset
-            let offset = u16::from_le_bytes([bytes[2], bytes[3]]) + (u16::MAX / 2);
-            // If the init_data flag is set
-            let init_data = if size & 0x8000 == 0x8000 {
-                Some(bytes[4..rsize as usize + 4].into())
+            let rsize = size & !INIT_DATA_FLAG;
+            // Offset is relative to the start of the variable segment
+            let offset = u16::from_le_bytes([bytes[2], bytes[3]]);
+            // If the init_data flag is set the data follows the item
+            let data_len = if size & INIT_DATA_FLAG != 0 { rsize } else { 0 };
+            if 4 + data_len as usize > bytes.len() {
+                return Err(FileError::TruncatedStorage { item });
+            }
+
+            let init_data = if size & INIT_DATA_FLAG != 0 {
+                Some(bytes[4..4 + data_len as usize].into())
             } else {
                 None
             };
+            bytes = &bytes[4 + data_len as usize..];
 
-            if 4 + rsize as usize > bytes.len() {
-                break;
+            let item_end = offset as usize + rsize as usize;
+            if item_end > memory::VARIABLE_SIZE as usize {
+                return Err(FileError::StorageOutOfRange { item });
             }
 
-            // If the init_data flag is set
-            bytes = if size & 0x8000 == 0x8000 {
-                &bytes[4 + rsize as usize..]
-            } else {
-                &bytes[4..]
-            };
-
             items.push(StorageItem {
                 offset,
                 init_data,
@@ -58,11 +78,234 @@ impl Storage {
             })
         }
 
-        Self { items, total_size }
+        if !bytes.is_empty() {
+            return Err(FileError::TrailingStorage { len: bytes.len() });
+        }
+
+        let storage = Self { items, total_size };
+        storage.check_overlap()?;
+        Ok(storage)
+    }
+
+    /// Items must not share any bytes of the variable segment
+    fn check_overlap(&self) -> Result<(), FileError> {
+        // Empty items don't take any bytes
+        let mut order: Vec<usize> = (0..self.items.len())
+            .filter(|&idx| self.items[idx].size != 0)
+            .collect();
+        order.sort_by_key(|&idx| self.items[idx].offset);
+
+        // Item that ends the furthest among the ones before, with its end
+        let mut furthest: Option<(usize, usize)> = None;
+        for idx in order {
+            let range = self.items[idx].range();
+            if let Some((prev, end)) = furthest {
+                if end > range.start {
+                    return Err(FileError::OverlappingStorage {
+                        first: prev as u16,
+                        second: idx as u16,
+                    });
+                }
+            }
+
+            if furthest.is_none_or(|(_, end)| end < range.end) {
+                furthest = Some((idx, range.end));
+            }
+        }
+        Ok(())
     }
 }
 
+/// Reasons why an object file can't be loaded or saved
 #[derive(Debug)]
+pub enum FileError {
+    /// Reading or writing the file failed
+    Io(io::Error),
+    /// File doesn't start with the [MAGIC] signature
+    NotObjectFile,
+    /// File was written by a different version of the format
+    UnsupportedVersion(u16),
+    /// Header has flags that this version doesn't know
+    UnknownFlags(u16),
+    /// Section sizes in the header don't add up to the file size
+    SizeMismatch { expected: usize, actual: usize },
+    /// Sections don't match the checksum in the header
+    ChecksumMismatch { expected: u32, actual: u32 },
+    /// Storage section ends in the middle of the item
+    TruncatedStorage { item: u16 },
+    /// Storage section has bytes after the last item
+    TrailingStorage { len: usize },
+    /// Item doesn't fit into the variable segment
+    StorageOutOfRange { item: u16 },
+    /// Two items are placed on the same bytes
+    OverlappingStorage { first: u16, second: u16 },
+    /// Instruction start address is outside of the code section
+    MainOutOfRange { main_start: u16, code_size: u32 },
+    /// Code section doesn't fit into the 16-bit address space,
+    /// the address after the last instruction has to fit too
+    CodeTooLarge { code_size: u32 },
+    /// Storage section or its item count doesn't fit into the header
+    StorageTooLarge { len: usize },
+    /// Item size uses the bit reserved for the initialised data flag
+    StorageItemTooLarge { item: u16, size: u16 },
+    /// Initialised data of the item isn't as long as the item
+    InitDataMismatch { item: u16, size: u16, len: usize },
+}
+
+impl std::fmt::Display for FileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{err}"),
+            Self::NotObjectFile => write!(f, "not a smol object file"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported object file version {version}")
+            }
+            Self::UnknownFlags(flags) => write!(f, "unknown flags {flags:#06x}"),
+            Self::SizeMismatch { expected, actual } => write!(
+                f,
+                "file should be {expected} bytes according to the header, was {actual}"
+            ),
+            Self::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "checksum {actual:#010x} doesn't match the header checksum {expected:#010x}"
+            ),
+            Self::TruncatedStorage { item } => write!(f, "storage item {item} is truncated"),
+            Self::TrailingStorage { len } => {
+                write!(f, "storage section has {len} bytes after the last item")
+            }
+            Self::StorageOutOfRange { item } => {
+                write!(
+                    f,
+                    "storage item {item} doesn't fit into the variable segment"
+                )
+            }
+            Self::OverlappingStorage { first, second } => {
+                write!(f, "storage items {first} and {second} overlap")
+            }
+            Self::MainOutOfRange {
+                main_start,
+                code_size,
+            } => write!(
+                f,
+                "start address {main_start} is outside of the {code_size} byte code section"
+            ),
+            Self::CodeTooLarge { code_size } => write!(
+                f,
+                "code section of {code_size} bytes doesn't fit into the address space"
+            ),
+            Self::StorageTooLarge { len } => {
+                write!(f, "storage section of {len} bytes is too large")
+            }
+            Self::StorageItemTooLarge { item, size } => write!(
+                f,
+                "storage item {item} of {size} bytes is larger than {} bytes",
+                INIT_DATA_FLAG - 1
+            ),
+            Self::InitDataMismatch { item, size, len } => write!(
+                f,
+                "storage item {item} is {size} bytes but has {len} bytes of data"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for FileError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+/// Signature at the start of every object file
+pub const MAGIC: [u8; 4] = *b"SMOL";
+/// Version of the file format written by [SmolFile::to_bytes]
+pub const FORMAT_VERSION: u16 = 1;
+/// Size of the [Header] in bytes
+pub const HEADER_SIZE: usize = 22;
+
+/// Header at the start of the object file, all values are little endian.
+///
+/// | Offset | Size | Field                          |
+/// |--------|------|--------------------------------|
+/// | 0      | 4    | Magic, `SMOL`                  |
+/// | 4      | 2    | Format version                 |
+/// | 6      | 2    | Flags, none are defined in v1  |
+/// | 8      | 2    | Number of storage items        |
+/// | 10     | 2    | Size of the storage section    |
+/// | 12     | 4    | Size of the code section       |
+/// | 16     | 2    | Instruction start address      |
+/// | 18     | 4    | CRC-32 of the sections         |
+///
+/// The storage section and the code section follow the header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    pub version: u16,
+    pub flags: u16,
+    pub storage_items: u16,
+    pub storage_size: u16,
+    pub code_size: u32,
+    pub main_start: u16,
+    pub checksum: u32,
+}
+
+impl Header {
+    pub fn to_bytes(&self) -> [u8; HEADER_SIZE] {
+        let mut bytes = [0; HEADER_SIZE];
+        bytes[0..4].copy_from_slice(&MAGIC);
+        bytes[4..6].copy_from_slice(&self.version.to_le_bytes());
+        bytes[6..8].copy_from_slice(&self.flags.to_le_bytes());
+        bytes[8..10].copy_from_slice(&self.storage_items.to_le_bytes());
+        bytes[10..12].copy_from_slice(&self.storage_size.to_le_bytes());
+        bytes[12..16].copy_from_slice(&self.code_size.to_le_bytes());
+        bytes[16..18].copy_from_slice(&self.main_start.to_le_bytes());
+        bytes[18..22].copy_from_slice(&self.checksum.to_le_bytes());
+        bytes
+    }
+
+    /// None if the bytes don't start with the magic signature
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < HEADER_SIZE || bytes[0..4] != MAGIC {
+            return None;
+        }
+
+        let u16_at = |idx: usize| u16::from_le_bytes([bytes[idx], bytes[idx + 1]]);
+        let u32_at = |idx: usize| {
+            u32::from_le_bytes([bytes[idx], bytes[idx + 1], bytes[idx + 2], bytes[idx + 3]])
+        };
+        Some(Self {
+            version: u16_at(4),
+            flags: u16_at(6),
+            storage_items: u16_at(8),
+            storage_size: u16_at(10),
+            code_size: u32_at(12),
+            main_start: u16_at(16),
+            checksum: u32_at(18),
+        })
+    }
+}
+
+/// CRC-32 (IEEE 802.3) of the bytes
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SmolFile {
     pub storage: Storage,
     /// Instruction start address
@@ -71,39 +314,180 @@ pub struct SmolFile {
 }
 
 impl SmolFile {
-    pub fn save(self, path: &str) {
-        let mut storage_bytes: Vec<u8> = Vec::new();
+    /// Object file with the header.
+    /// Fails if a field doesn't fit into the format instead of writing a corrupted file.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, FileError> {
+        let mut sections: Vec<u8> = Vec::new();
 
         // Storage
-        storage_bytes.extend(self.storage.total_size.to_le_bytes().iter());
-        for item in self.storage.items {
-            storage_bytes.extend(item.size.to_le_bytes().iter());
-            storage_bytes.extend(item.offset.to_le_bytes().iter());
-            if let Some(data) = item.init_data {
-                storage_bytes.extend(data.iter());
+        let storage_items =
+            u16::try_from(self.storage.items.len()).map_err(|_| FileError::StorageTooLarge {
+                len: self.storage.items.len(),
+            })?;
+        for (idx, item) in self.storage.items.iter().enumerate() {
+            let idx = idx as u16;
+            if item.size & INIT_DATA_FLAG != 0 {
+                return Err(FileError::StorageItemTooLarge {
+                    item: idx,
+                    size: item.size,
+                });
+            }
+
+            let size = match &item.init_data {
+                Some(data) if data.len() != item.size as usize => {
+                    return Err(FileError::InitDataMismatch {
+                        item: idx,
+                        size: item.size,
+                        len: data.len(),
+                    });
+                }
+                Some(_) => item.size | INIT_DATA_FLAG,
+                None => item.size,
+            };
+            sections.extend(size.to_le_bytes().iter());
+            sections.extend(item.offset.to_le_bytes().iter());
+            if let Some(data) = &item.init_data {
+                sections.extend(data.iter());
             }
         }
+        let storage_size =
+            u16::try_from(sections.len()).map_err(|_| FileError::StorageTooLarge {
+                len: sections.len(),
+            })?;
 
-        // start addess
-        storage_bytes.extend(self.main_start.to_le_bytes().iter());
+        let code_size = self.instructions.len() as u32;
+        if code_size as usize >= memory::MEMORY_SIZE {
+            return Err(FileError::CodeTooLarge { code_size });
+        }
 
         // instructions
-        storage_bytes.extend(self.instructions.iter());
+        sections.extend(self.instructions.iter());
+
+        let header = Header {
+            version: FORMAT_VERSION,
+            flags: 0,
+            storage_items,
+            storage_size,
+            code_size,
+            main_start: self.main_start,
+            checksum: crc32(&sections),
+        };
 
-        fs::write(path, storage_bytes).unwrap();
+        let mut file_bytes = header.to_bytes().to_vec();
+        file_bytes.extend(sections);
+        Ok(file_bytes)
     }
 
-    pub fn load(path: &str) -> Self {
-        let file_bytes = fs::read(path).unwrap();
-        let storage_size = u16::from_le_bytes([file_bytes[0], file_bytes[1]]) as usize;
-        let storage = Storage::load(&file_bytes);
-        let main_start = u16::from_le_bytes([file_bytes[storage_size + 2], file_bytes[storage_size + 3]]);
-        let instructions: Vec<u8> = file_bytes[storage_size + 4..].into();
+    /// Parse and validate the object file
+    pub fn from_bytes(file_bytes: &[u8]) -> Result<Self, FileError> {
+        let header = Header::parse(file_bytes).ok_or(FileError::NotObjectFile)?;
+        if header.version != FORMAT_VERSION {
+            return Err(FileError::UnsupportedVersion(header.version));
+        }
+        if header.flags != 0 {
+            return Err(FileError::UnknownFlags(header.flags));
+        }
+        if header.code_size as usize >= memory::MEMORY_SIZE {
+            return Err(FileError::CodeTooLarge {
+                code_size: header.code_size,
+            });
+        }
+
+        let storage_size = header.storage_size as usize;
+        let expected = HEADER_SIZE + storage_size + header.code_size as usize;
+        if file_bytes.len() != expected {
+            return Err(FileError::SizeMismatch {
+                expected,
+                actual: file_bytes.len(),
+            });
+        }
+
+        let sections = &file_bytes[HEADER_SIZE..];
+        let checksum = crc32(sections);
+        if checksum != header.checksum {
+            return Err(FileError::ChecksumMismatch {
+                expected: header.checksum,
+                actual: checksum,
+            });
+        }
+
+        // Empty program can only start from the beginning
+        let code_size = header.code_size;
+        if header.main_start as u32 >= code_size.max(1) {
+            return Err(FileError::MainOutOfRange {
+                main_start: header.main_start,
+                code_size,
+            });
+        }
 
-        Self {
+        let storage = Storage::load(header.storage_items, &sections[..storage_size])?;
+        let instructions: Vec<u8> = sections[storage_size..].into();
+
+        Ok(Self {
             storage,
-            main_start,
+            main_start: header.main_start,
             instructions,
-        }
+        })
+    }
+
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<(), FileError> {
+        writer.write_all(&self.to_bytes()?)?;
+        Ok(())
+    }
+
+    /// Read until the end of the reader, the object file has no terminator
+    pub fn read_from<R: Read>(mut reader: R) -> Result<Self, FileError> {
+        let mut file_bytes = Vec::new();
+        reader.read_to_end(&mut file_bytes)?;
+        Self::from_bytes(&file_bytes)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), FileError> {
+        fs::write(path, self.to_bytes()?)?;
+        Ok(())
     }
+
+    /// Load and validate the object file
+    pub fn load(path: &str) -> Result<Self, FileError> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+}
+
+/// Label address in the instructions.
+/// Symbols are saved next to the object file so debuggers can resolve labels.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    pub address: u16,
+}
+
+/// Save symbols as lines of hex address and name
+pub fn save_symbols(path: &str, symbols: &[Symbol]) -> io::Result<()> {
+    let lines: String = symbols
+        .iter()
+        .map(|sym| format!("{:04x} {}\n", sym.address, sym.name))
+        .collect();
+
+    fs::write(path, lines)
+}
+
+pub fn load_symbols(path: &str) -> io::Result<Vec<Symbol>> {
+    let contents = fs::read_to_string(path)?;
+    let mut symbols: Vec<Symbol> = Vec::new();
+    for line in contents.lines() {
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid symbol '{line}'"),
+            )
+        };
+        let (address, name) = line.split_once(' ').ok_or_else(invalid)?;
+        let address = u16::from_str_radix(address, 16).map_err(|_| invalid())?;
+        symbols.push(Symbol {
+            name: name.into(),
+            address,
+        });
+    }
+
+    Ok(symbols)
 }
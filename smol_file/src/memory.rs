@@ -0,0 +1,30 @@
+//! Default memory map of the VM.
+//!
+//! | Range             | Segment                                 |
+//! |-------------------|-----------------------------------------|
+//! | `0x0000..0x7000`  | Stack, grows up from address 0          |
+//! | `0x7000..0x8000`  | Guard region, every access faults       |
+//! | `0x8000..0xFF00`  | Variables, storage offsets start here   |
+//! | `0xFF00..0xFFE0`  | Memory mapped devices                   |
+//! | `0xFFE0..0xFFF0`  | Interrupt vector table                  |
+//! | `0xFFF0..0x10000` | Free                                    |
+//!
+//! Addresses are 16 bits, accesses that go past `0xFFFF` fault instead of wrapping around.
+
+/// Size of the memory in bytes, the whole 16-bit address space
+pub const MEMORY_SIZE: usize = 0x10000;
+
+/// Size of the stack segment that starts from address 0
+pub const STACK_SIZE: u16 = 0x7000;
+/// Size of the guard region between the stack and the variables
+pub const GUARD_SIZE: u16 = 0x1000;
+
+/// Base address of the variables, storage offsets are relative to it
+pub const VARIABLE_START: u16 = STACK_SIZE + GUARD_SIZE;
+/// Space for the variables before the device registers
+pub const VARIABLE_SIZE: u16 = IO_START - VARIABLE_START;
+
+/// Start of the memory mapped device registers
+pub const IO_START: u16 = 0xFF00;
+/// Start of the interrupt vector table, a 16-bit handler address per line
+pub const IVT_START: u16 = 0xFFE0;
@@ -0,0 +1,184 @@
+use std::fs;
+
+use smol_file::{
+    crc32, FileError, Header, SmolFile, Storage, StorageItem, FORMAT_VERSION, HEADER_SIZE,
+};
+
+fn temp_path(name: &str) -> String {
+    let dir = std::env::temp_dir().join(format!("smol-file-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir.join(name).to_string_lossy().into_owned()
+}
+
+fn example() -> SmolFile {
+    SmolFile {
+        storage: Storage {
+            total_size: 12,
+            items: vec![
+                StorageItem {
+                    size: 4,
+                    offset: 0,
+                    init_data: Some(vec![1, 2, 3, 4]),
+                },
+                StorageItem {
+                    size: 8,
+                    offset: 4,
+                    init_data: None,
+                },
+            ],
+        },
+        main_start: 1,
+        instructions: vec![1, 2, 3, 4],
+    }
+}
+
+#[test]
+pub fn it_computes_crc32() {
+    assert_eq!(crc32(b""), 0);
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+}
+
+#[test]
+pub fn it_writes_the_header() {
+    let path = temp_path("header.obj");
+    example().save(&path).unwrap();
+
+    let bytes = fs::read(&path).unwrap();
+    assert_eq!(&bytes[0..4], b"SMOL");
+    let header = Header::parse(&bytes).unwrap();
+    assert_eq!(
+        header,
+        Header {
+            version: FORMAT_VERSION,
+            flags: 0,
+            storage_items: 2,
+            storage_size: 12,
+            code_size: 4,
+            main_start: 1,
+            checksum: crc32(&bytes[HEADER_SIZE..]),
+        }
+    );
+    assert_eq!(Header::parse(&header.to_bytes()), Some(header));
+}
+
+#[test]
+pub fn it_loads_saved_file() {
+    let path = temp_path("roundtrip.obj");
+    example().save(&path).unwrap();
+
+    let file = SmolFile::load(&path).unwrap();
+    assert_eq!(file.main_start, 1);
+    assert_eq!(file.instructions, example().instructions);
+    assert_eq!(file.storage.items.len(), 2);
+    assert_eq!(file.storage.items[0].init_data, Some(vec![1, 2, 3, 4]));
+    assert_eq!(file.storage.items[1].size, 8);
+    assert_eq!(file.storage.items[1].offset, 4);
+}
+
+#[test]
+pub fn it_rejects_files_without_magic() {
+    assert_eq!(Header::parse(b"\x00\x00\x00\x00 not an object file"), None);
+    assert_eq!(Header::parse(b"SMOL"), None);
+}
+
+#[test]
+pub fn it_refuses_unknown_versions() {
+    let path = temp_path("version.obj");
+    example().save(&path).unwrap();
+
+    let mut bytes = fs::read(&path).unwrap();
+    bytes[4..6].copy_from_slice(&2u16.to_le_bytes());
+    fs::write(&path, bytes).unwrap();
+
+    assert!(matches!(
+        SmolFile::load(&path),
+        Err(FileError::UnsupportedVersion(2))
+    ));
+}
+
+#[test]
+pub fn it_refuses_corrupted_files() {
+    let path = temp_path("corrupt.obj");
+    example().save(&path).unwrap();
+
+    let mut bytes = fs::read(&path).unwrap();
+    let last = bytes.len() - 1;
+    bytes[last] ^= 0xFF;
+    fs::write(&path, bytes).unwrap();
+
+    assert!(matches!(
+        SmolFile::load(&path),
+        Err(FileError::ChecksumMismatch { .. })
+    ));
+}
+
+#[test]
+pub fn it_roundtrips_bytes() {
+    let bytes = example().to_bytes().unwrap();
+    assert_eq!(&bytes[0..4], b"SMOL");
+    assert_eq!(SmolFile::from_bytes(&bytes).unwrap(), example());
+}
+
+#[test]
+pub fn it_roundtrips_through_reader_and_writer() {
+    let mut stream: Vec<u8> = Vec::new();
+    example().write_to(&mut stream).unwrap();
+    assert_eq!(stream, example().to_bytes().unwrap());
+
+    let file = SmolFile::read_from(stream.as_slice()).unwrap();
+    assert_eq!(file, example());
+}
+
+#[test]
+pub fn it_fails_to_read_garbage() {
+    let result = SmolFile::read_from(&b"#!/bin/sh"[..]);
+    assert!(matches!(result, Err(FileError::NotObjectFile)));
+}
+
+#[test]
+pub fn it_refuses_to_write_invalid_storage() {
+    let mut file = example();
+    file.storage.items[1].size = 0x8000;
+    assert!(matches!(
+        file.to_bytes(),
+        Err(FileError::StorageItemTooLarge {
+            item: 1,
+            size: 0x8000
+        })
+    ));
+
+    let mut file = example();
+    file.storage.items[0].init_data = Some(vec![1, 2]);
+    assert!(matches!(
+        file.to_bytes(),
+        Err(FileError::InitDataMismatch {
+            item: 0,
+            size: 4,
+            len: 2
+        })
+    ));
+
+    // Two items of 0x7FFF bytes take more than the 16-bit section size
+    let mut file = example();
+    file.storage.items = (0..2)
+        .map(|idx| StorageItem {
+            size: 0x7FFF,
+            offset: idx * 0x7FFF,
+            init_data: Some(vec![0; 0x7FFF]),
+        })
+        .collect();
+    assert!(matches!(
+        file.to_bytes(),
+        Err(FileError::StorageTooLarge { len: 0x10006 })
+    ));
+}
+
+#[test]
+pub fn it_refuses_to_write_too_much_code() {
+    let mut file = example();
+    file.instructions = vec![0; 0x10000];
+    assert!(matches!(
+        file.save(&temp_path("large.obj")),
+        Err(FileError::CodeTooLarge { code_size: 0x10000 })
+    ));
+}
@@ -0,0 +1,187 @@
+use std::fs;
+
+use smol_file::{crc32, FileError, Header, SmolFile, FORMAT_VERSION};
+
+/// Write an object file with a valid header around the raw sections
+fn write_file(name: &str, items: u16, storage: &[u8], code: &[u8], main_start: u16) -> String {
+    let dir = std::env::temp_dir().join(format!("smol-load-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name).to_string_lossy().into_owned();
+
+    let mut sections = storage.to_vec();
+    sections.extend(code);
+    let header = Header {
+        version: FORMAT_VERSION,
+        flags: 0,
+        storage_items: items,
+        storage_size: storage.len() as u16,
+        code_size: code.len() as u32,
+        main_start,
+        checksum: crc32(&sections),
+    };
+
+    let mut bytes = header.to_bytes().to_vec();
+    bytes.extend(sections);
+    fs::write(&path, bytes).unwrap();
+    path
+}
+
+#[test]
+pub fn it_fails_on_missing_file() {
+    let result = SmolFile::load("/nonexistent/smol/file.obj");
+    assert!(matches!(result, Err(FileError::Io(_))));
+}
+
+#[test]
+pub fn it_fails_on_truncated_header() {
+    let path = write_file("short.obj", 0, &[], &[0], 0);
+    let bytes = fs::read(&path).unwrap();
+    fs::write(&path, &bytes[..10]).unwrap();
+
+    assert!(matches!(
+        SmolFile::load(&path),
+        Err(FileError::NotObjectFile)
+    ));
+}
+
+#[test]
+pub fn it_fails_on_truncated_code() {
+    let path = write_file("code.obj", 0, &[], &[1, 2, 3], 0);
+    let bytes = fs::read(&path).unwrap();
+    fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+
+    assert!(matches!(
+        SmolFile::load(&path),
+        Err(FileError::SizeMismatch {
+            expected: 25,
+            actual: 24
+        })
+    ));
+}
+
+#[test]
+pub fn it_fails_on_truncated_storage_item() {
+    // Initialised item of 4 bytes with only 2 bytes of data
+    let storage = [0x04, 0x80, 0x00, 0x00, 1, 2];
+    let path = write_file("item.obj", 1, &storage, &[0], 0);
+
+    assert!(matches!(
+        SmolFile::load(&path),
+        Err(FileError::TruncatedStorage { item: 0 })
+    ));
+}
+
+#[test]
+pub fn it_fails_on_missing_storage_items() {
+    // Header says there are two items but the section has one
+    let storage = [0x04, 0x00, 0x00, 0x00];
+    let path = write_file("missing.obj", 2, &storage, &[0], 0);
+
+    assert!(matches!(
+        SmolFile::load(&path),
+        Err(FileError::TruncatedStorage { item: 1 })
+    ));
+}
+
+#[test]
+pub fn it_fails_on_extra_storage_bytes() {
+    let storage = [0x04, 0x00, 0x00, 0x00, 0xFF];
+    let path = write_file("extra.obj", 1, &storage, &[0], 0);
+
+    assert!(matches!(
+        SmolFile::load(&path),
+        Err(FileError::TrailingStorage { len: 1 })
+    ));
+}
+
+#[test]
+pub fn it_fails_on_overlapping_storage() {
+    // 4 bytes at offset 2 and 4 bytes at offset 0
+    let storage = [0x04, 0x00, 0x02, 0x00, 0x04, 0x00, 0x00, 0x00];
+    let path = write_file("overlap.obj", 2, &storage, &[0], 0);
+
+    assert!(matches!(
+        SmolFile::load(&path),
+        Err(FileError::OverlappingStorage {
+            first: 1,
+            second: 0
+        })
+    ));
+}
+
+#[test]
+pub fn it_fails_on_storage_outside_of_variables() {
+    // 0x100 bytes at offset 0x7F00
+    let storage = [0x00, 0x01, 0x00, 0x7F];
+    let path = write_file("range.obj", 1, &storage, &[0], 0);
+
+    assert!(matches!(
+        SmolFile::load(&path),
+        Err(FileError::StorageOutOfRange { item: 0 })
+    ));
+}
+
+#[test]
+pub fn it_fails_on_start_outside_of_code() {
+    let path = write_file("main.obj", 0, &[], &[1, 2], 2);
+
+    assert!(matches!(
+        SmolFile::load(&path),
+        Err(FileError::MainOutOfRange {
+            main_start: 2,
+            code_size: 2
+        })
+    ));
+}
+
+#[test]
+pub fn it_loads_adjacent_storage() {
+    // Initialised 2 bytes at offset 0 and reserved 4 bytes at offset 2
+    let storage = [0x02, 0x80, 0x00, 0x00, 7, 8, 0x04, 0x00, 0x02, 0x00];
+    let path = write_file("adjacent.obj", 2, &storage, &[], 0);
+
+    let file = SmolFile::load(&path).unwrap();
+    assert_eq!(file.storage.items.len(), 2);
+    assert_eq!(file.storage.items[0].size, 2);
+    assert_eq!(file.storage.items[0].init_data, Some(vec![7, 8]));
+    assert_eq!(file.storage.items[1].init_data, None);
+}
+
+#[test]
+pub fn it_fails_on_code_outside_of_address_space() {
+    // The end of the code would be at 0x10000, which ic can't hold
+    let path = write_file("large.obj", 0, &[], &vec![0; 0x10000], 0);
+    assert!(matches!(
+        SmolFile::load(&path),
+        Err(FileError::CodeTooLarge { code_size: 0x10000 })
+    ));
+
+    let path = write_file("largest.obj", 0, &[], &vec![0; 0xFFFF], 0);
+    assert_eq!(SmolFile::load(&path).unwrap().instructions.len(), 0xFFFF);
+}
+
+#[test]
+pub fn it_fails_on_storage_inside_of_another_item() {
+    // 100 bytes at offset 0, empty item at offset 5 and 10 bytes at offset 50
+    let storage = [
+        100, 0x00, 0x00, 0x00, 0x00, 0x00, 0x05, 0x00, 10, 0x00, 50, 0x00,
+    ];
+    let path = write_file("inside.obj", 3, &storage, &[0], 0);
+
+    assert!(matches!(
+        SmolFile::load(&path),
+        Err(FileError::OverlappingStorage {
+            first: 0,
+            second: 2
+        })
+    ));
+}
+
+#[test]
+pub fn it_loads_empty_storage_inside_of_another_item() {
+    // 100 bytes at offset 0 and an empty item at offset 5
+    let storage = [100, 0x00, 0x00, 0x00, 0x00, 0x00, 0x05, 0x00];
+    let path = write_file("empty.obj", 2, &storage, &[0], 0);
+
+    assert_eq!(SmolFile::load(&path).unwrap().storage.items.len(), 2);
+}
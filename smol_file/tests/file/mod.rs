@@ -0,0 +1,3 @@
+mod header_test;
+mod load_test;
+mod symbol_test;
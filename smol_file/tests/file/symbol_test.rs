@@ -0,0 +1,30 @@
+use smol_file::{load_symbols, save_symbols, Symbol};
+
+#[test]
+pub fn it_saves_and_loads_symbols() {
+    let dir = std::env::temp_dir().join(format!("smol-symbols-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("main.sym").to_string_lossy().into_owned();
+    let symbols = vec![
+        Symbol {
+            name: "main".into(),
+            address: 0,
+        },
+        Symbol {
+            name: "loop".into(),
+            address: 0x1234,
+        },
+    ];
+
+    save_symbols(&path, &symbols).unwrap();
+    assert_eq!(load_symbols(&path).unwrap(), symbols);
+}
+
+#[test]
+pub fn it_fails_to_save_symbols_to_missing_directory() {
+    let symbols = [Symbol {
+        name: "main".into(),
+        address: 0,
+    }];
+    assert!(save_symbols("/nonexistent/smol/main.sym", &symbols).is_err());
+}
@@ -0,0 +1,39 @@
+use std::process::exit;
+
+use smol_vm::disasm;
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 2 {
+        println!("Give file as an argument");
+        exit(1);
+    }
+
+    let file = match smol_file::SmolFile::load(&args[1]) {
+        Ok(file) => file,
+        Err(err) => {
+            eprintln!("Error: {}: {err}", args[1]);
+            exit(1);
+        }
+    };
+    // Symbols are optional, missing labels are generated from the branch targets
+    let sym_path = format!("{}.sym", args[1].trim_end_matches(".obj"));
+    let symbols = smol_file::load_symbols(&sym_path).unwrap_or_default();
+
+    for item in &file.storage.items {
+        let kind = if item.init_data.is_some() {
+            "initialised"
+        } else {
+            "reserved"
+        };
+        println!(
+            "# {kind} variable at vp+{:#06x}, {} bytes",
+            item.offset, item.size
+        );
+    }
+
+    print!(
+        "{}",
+        disasm::listing(&file.instructions, file.main_start, &symbols)
+    );
+}
@@ -0,0 +1,304 @@
+use std::io::{BufRead, Write};
+
+use smol_file::Symbol;
+
+use crate::{disasm, registers::FG_NAMES, ExitReason, Vm, VmError};
+
+const HELP: &str = "\
+Commands:
+  b, break <addr|label>     Add a breakpoint
+  d, delete <addr|label>    Remove a breakpoint
+  bl, breakpoints           List breakpoints
+  s, step [count]           Execute count instructions, defaults to 1
+  c, continue               Run until a breakpoint or the end of the program
+  r, regs                   Show registers
+  x, mem <addr> [len]       Hexdump memory, len defaults to 64
+  bt, backtrace             Show the call stack
+  h, help                   Show this help
+  q, quit                   Quit the debugger";
+
+/// Call frame created by the `call` instruction or by entering an interrupt handler
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    /// Stack address where the return address was pushed
+    return_slot: u16,
+}
+
+/// Interactive debugger that drives a [Vm] one instruction at a time
+pub struct Debugger {
+    pub vm: Vm,
+    /// Labels sorted by address
+    symbols: Vec<Symbol>,
+    breakpoints: Vec<u16>,
+    frames: Vec<Frame>,
+}
+
+impl Debugger {
+    pub fn new(vm: Vm, symbols: Vec<Symbol>) -> Self {
+        let mut symbols = symbols;
+        symbols.sort_by_key(|sym| sym.address);
+        Self {
+            vm,
+            symbols,
+            breakpoints: Vec::new(),
+            frames: Vec::new(),
+        }
+    }
+
+    /// Resolve a label name or a decimal/hexadecimal (0x) address
+    pub fn resolve(&self, target: &str) -> Option<u16> {
+        if let Some(sym) = self.symbols.iter().find(|sym| sym.name == target) {
+            return Some(sym.address);
+        }
+
+        parse_number(target)
+    }
+
+    /// Format the address with the closest preceding label
+    pub fn symbolize(&self, address: u16) -> String {
+        match self.symbols.iter().rev().find(|sym| sym.address <= address) {
+            Some(sym) => format!("{address:#06x} <{}+{}>", sym.name, address - sym.address),
+            None => format!("{address:#06x}"),
+        }
+    }
+
+    /// Current address followed by the disassembled instruction
+    fn location(&self) -> String {
+        let ic = self.vm.registers.ic;
+        let instructions = &self.vm.instructions.instructions;
+        if ic as usize >= instructions.len() {
+            return self.symbolize(ic);
+        }
+
+        let instr = disasm::decode(instructions, ic);
+        format!(
+            "{}: {}",
+            self.symbolize(ic),
+            disasm::format_instruction(&instr, &self.symbols)
+        )
+    }
+
+    pub fn add_breakpoint(&mut self, address: u16) {
+        if !self.breakpoints.contains(&address) {
+            self.breakpoints.push(address);
+        }
+    }
+
+    /// Returns false if there was no breakpoint at the address
+    pub fn remove_breakpoint(&mut self, address: u16) -> bool {
+        let len = self.breakpoints.len();
+        self.breakpoints.retain(|addr| *addr != address);
+        len != self.breakpoints.len()
+    }
+
+    /// Execute one instruction while keeping track of the call frames
+    pub fn step(&mut self) -> Result<Option<ExitReason>, VmError> {
+        let ic = self.vm.registers.ic;
+        let sp = self.vm.registers.sp;
+        let opcode = self.vm.instructions.instructions.get(ic as usize).copied();
+        // Entering an interrupt handler doesn't execute the instruction at `ic`
+        let interrupted = opcode.is_some() && self.vm.takes_interrupt();
+
+        let ret = self.vm.step()?;
+
+        match opcode {
+            // The return address is pushed before the flags
+            _ if interrupted => self.frames.push(Frame { return_slot: sp }),
+            // Call, but not syscall
+            Some(op) if op >> 3 == 0b11_101 && op & 0b111 != 0b111 => {
+                self.frames.push(Frame { return_slot: sp })
+            }
+            // Return from call or from interrupt
+            Some(op) if op >> 3 == 0b11_110 || op == 0b11_111_000 => {
+                self.frames.pop();
+            }
+            _ => {}
+        }
+
+        Ok(ret)
+    }
+
+    /// Run until a breakpoint is hit or the program stops.
+    /// Hitting a breakpoint returns [ExitReason::Predicate].
+    pub fn resume(&mut self) -> Result<ExitReason, VmError> {
+        loop {
+            if let Some(reason) = self.step()? {
+                return Ok(reason);
+            }
+
+            if self.breakpoints.contains(&self.vm.registers.ic) {
+                return Ok(ExitReason::Predicate);
+            }
+        }
+    }
+
+    /// Current instruction followed by the return addresses of the call frames
+    pub fn backtrace(&self) -> Vec<u16> {
+        let memory = self.vm.stack.memory();
+        let mut trace = vec![self.vm.registers.ic];
+        for frame in self.frames.iter().rev() {
+            let slot = frame.return_slot as usize;
+            match memory.get(slot..slot + 2) {
+                Some(bytes) => trace.push(u16::from_le_bytes([bytes[0], bytes[1]])),
+                None => break,
+            }
+        }
+
+        trace
+    }
+
+    fn write_registers<W: Write>(&self, out: &mut W) -> std::io::Result<()> {
+        let regs = &self.vm.registers;
+        let flags: Vec<&str> = FG_NAMES
+            .iter()
+            .filter(|(flag, _)| regs.fg & flag != 0)
+            .map(|(_, name)| *name)
+            .collect();
+
+        writeln!(
+            out,
+            "ic {:#06x}  fg {:#06x} [{}]",
+            regs.ic,
+            regs.fg,
+            flags.join(" ")
+        )?;
+        writeln!(
+            out,
+            "cr {:#06x}  sp {:#06x}  vp {:#06x}  zr {:#06x}",
+            regs.cr, regs.sp, regs.vp, regs.zr
+        )?;
+        writeln!(out, "l0 {:#06x}  l1 {:#06x}", regs.l0, regs.l1)?;
+        writeln!(
+            out,
+            "r0 {:#04x}  r1 {:#04x}  r2 {:#04x}  r3 {:#04x}  r4 {:#04x}  r5 {:#04x}  r6 {:#04x}  r7 {:#04x}",
+            regs.r0, regs.r1, regs.r2, regs.r3, regs.r4, regs.r5, regs.r6, regs.r7
+        )
+    }
+
+    fn write_memory<W: Write>(&self, out: &mut W, start: u16, len: usize) -> std::io::Result<()> {
+        let memory = self.vm.stack.memory();
+        let start = start as usize;
+        let end = (start + len).min(memory.len());
+        let mut addr = start;
+        while addr < end {
+            let row = &memory[addr..(addr + 16).min(end)];
+            let hex: Vec<String> = row.iter().map(|byte| format!("{byte:02x}")).collect();
+            let ascii: String = row
+                .iter()
+                .map(|byte| match byte {
+                    0x20..=0x7e => *byte as char,
+                    _ => '.',
+                })
+                .collect();
+            writeln!(out, "{addr:04x}: {:<47}  |{ascii}|", hex.join(" "))?;
+            addr += 16;
+        }
+
+        Ok(())
+    }
+
+    fn write_stop<W: Write>(
+        &self,
+        out: &mut W,
+        ret: Result<Option<ExitReason>, VmError>,
+    ) -> std::io::Result<()> {
+        match ret {
+            Ok(Some(ExitReason::Finished)) => writeln!(out, "Program finished"),
+            Ok(Some(ExitReason::Exited(status))) => {
+                writeln!(out, "Program exited with status {status}")
+            }
+            Ok(Some(ExitReason::Predicate)) => writeln!(out, "Breakpoint at {}", self.location()),
+            Ok(_) => writeln!(out, "Stopped at {}", self.location()),
+            Err(err) => writeln!(out, "Error: {err}"),
+        }
+    }
+
+    /// Read commands from `input` until it ends or `quit` is given
+    pub fn repl<R: BufRead, W: Write>(&mut self, input: R, mut out: W) -> std::io::Result<()> {
+        let out = &mut out;
+        writeln!(out, "Stopped at {}", self.location())?;
+        write!(out, "(smol-dbg) ")?;
+        out.flush()?;
+
+        for line in input.lines() {
+            let line = line?;
+            let args: Vec<&str> = line.split_ascii_whitespace().collect();
+            match args.as_slice() {
+                [] => {}
+                ["b" | "break", target] => match self.resolve(target) {
+                    Some(addr) => {
+                        self.add_breakpoint(addr);
+                        writeln!(out, "Breakpoint at {}", self.symbolize(addr))?;
+                    }
+                    None => writeln!(out, "Unknown address or label '{target}'")?,
+                },
+                ["d" | "delete", target] => match self.resolve(target) {
+                    Some(addr) if self.remove_breakpoint(addr) => {
+                        writeln!(out, "Removed breakpoint at {}", self.symbolize(addr))?
+                    }
+                    Some(addr) => writeln!(out, "No breakpoint at {}", self.symbolize(addr))?,
+                    None => writeln!(out, "Unknown address or label '{target}'")?,
+                },
+                ["bl" | "breakpoints"] => {
+                    for addr in &self.breakpoints {
+                        writeln!(out, "{}", self.symbolize(*addr))?;
+                    }
+                }
+                ["s" | "step", rest @ ..] => {
+                    let count = match rest.first() {
+                        Some(count) => match parse_number(count) {
+                            Some(count) => count,
+                            None => {
+                                writeln!(out, "Invalid step count '{count}'")?;
+                                continue;
+                            }
+                        },
+                        None => 1,
+                    };
+
+                    let mut ret = Ok(None);
+                    for _ in 0..count {
+                        ret = self.step();
+                        if !matches!(ret, Ok(None)) {
+                            break;
+                        }
+                    }
+                    self.write_stop(out, ret)?;
+                }
+                ["c" | "continue"] => {
+                    let ret = self.resume().map(Some);
+                    self.write_stop(out, ret)?;
+                }
+                ["r" | "regs"] => self.write_registers(out)?,
+                ["x" | "mem", addr, rest @ ..] => {
+                    let len = rest.first().and_then(|len| parse_number(len)).unwrap_or(64);
+                    match parse_number(addr) {
+                        Some(addr) => self.write_memory(out, addr, len as usize)?,
+                        None => writeln!(out, "Invalid address '{addr}'")?,
+                    }
+                }
+                ["bt" | "backtrace"] => {
+                    for (idx, addr) in self.backtrace().iter().enumerate() {
+                        writeln!(out, "#{idx} {}", self.symbolize(*addr))?;
+                    }
+                }
+                ["h" | "help"] => writeln!(out, "{HELP}")?,
+                ["q" | "quit"] => break,
+                _ => writeln!(out, "Unknown command '{line}', try 'help'")?,
+            }
+
+            write!(out, "(smol-dbg) ")?;
+            out.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parse decimal or hexadecimal (0x) number
+fn parse_number(value: &str) -> Option<u16> {
+    match value.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => value.parse::<u16>().ok(),
+    }
+}
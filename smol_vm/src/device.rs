@@ -0,0 +1,291 @@
+use std::io::{Read, Write};
+
+use smol_file::memory::IO_START;
+
+use crate::INTERRUPT_LINES;
+
+/// Start of the [Console] registers in the default memory map
+pub const CONSOLE_BASE: u16 = IO_START;
+/// Start of the [Timer] registers in the default memory map
+pub const TIMER_BASE: u16 = IO_START + 0x10;
+/// Start of the [Random] registers in the default memory map
+pub const RANDOM_BASE: u16 = IO_START + 0x20;
+/// Interrupt line of the [Timer] in the default memory map
+pub const TIMER_LINE: u8 = 0;
+
+/// Host-side peripheral mapped into the guest memory by the [Bus].
+/// Offsets are relative to the start of the mapped range.
+pub trait Device {
+    fn read(&mut self, offset: u16) -> u8;
+    fn write(&mut self, offset: u16, value: u8);
+
+    /// Called after every instruction, returns the interrupt line to raise
+    fn tick(&mut self) -> Option<u8> {
+        None
+    }
+}
+
+/// Address range that can't be mapped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapError {
+    /// Range overlaps with an already mapped device
+    Overlap { start: u16, len: u16 },
+    /// Range is empty or goes past the end of the address space
+    InvalidRange { start: u16, len: u16 },
+}
+
+impl std::fmt::Display for MapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::Overlap { start, len } => write!(
+                f,
+                "address range {start:#06x}+{len} overlaps with a mapped device"
+            ),
+            Self::InvalidRange { start, len } => {
+                write!(f, "address range {start:#06x}+{len} is invalid")
+            }
+        }
+    }
+}
+
+impl std::error::Error for MapError {}
+
+struct Mapping {
+    start: u16,
+    len: u16,
+    device: Box<dyn Device>,
+}
+
+impl Mapping {
+    fn contains(&self, addr: u16) -> bool {
+        addr >= self.start && (addr - self.start) < self.len
+    }
+}
+
+/// Maps address ranges to devices, addresses outside of every range go to the memory
+#[derive(Default)]
+pub struct Bus {
+    mappings: Vec<Mapping>,
+}
+
+impl Bus {
+    /// Map `len` bytes starting from `start` to the device
+    pub fn map(
+        &mut self,
+        start: u16,
+        len: u16,
+        device: impl Device + 'static,
+    ) -> Result<(), MapError> {
+        if len == 0 || start.checked_add(len - 1).is_none() {
+            return Err(MapError::InvalidRange { start, len });
+        }
+
+        let end = start + (len - 1);
+        let overlaps = self.mappings.iter().any(|map| {
+            let map_end = map.start + (map.len - 1);
+            start <= map_end && map.start <= end
+        });
+        if overlaps {
+            return Err(MapError::Overlap { start, len });
+        }
+
+        self.mappings.push(Mapping {
+            start,
+            len,
+            device: Box::new(device),
+        });
+        Ok(())
+    }
+
+    pub fn is_mapped(&self, addr: u16) -> bool {
+        self.mappings.iter().any(|map| map.contains(addr))
+    }
+
+    /// Device mapped at the address with the offset into its range
+    pub fn device_at(&mut self, addr: u16) -> Option<(&mut dyn Device, u16)> {
+        let map = self.mappings.iter_mut().find(|map| map.contains(addr))?;
+        Some((map.device.as_mut(), addr - map.start))
+    }
+
+    /// Tick every device, `raise` is called with the interrupt lines they return
+    pub fn tick<F: FnMut(u8)>(&mut self, mut raise: F) {
+        for map in &mut self.mappings {
+            if let Some(line) = map.device.tick() {
+                raise(line);
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for Bus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ranges: Vec<String> = self
+            .mappings
+            .iter()
+            .map(|map| format!("{:#06x}+{}", map.start, map.len))
+            .collect();
+        f.debug_struct("Bus").field("mappings", &ranges).finish()
+    }
+}
+
+/// Character device.
+///
+/// * `0` - Data, reading takes the next input byte, writing outputs the byte
+/// * `1` - Status, bit 0 is set once the input has ended
+pub struct Console {
+    input: Box<dyn Read>,
+    output: Box<dyn Write>,
+    eof: bool,
+}
+
+impl Console {
+    pub fn new(input: impl Read + 'static, output: impl Write + 'static) -> Self {
+        Self {
+            input: Box::new(input),
+            output: Box::new(output),
+            eof: false,
+        }
+    }
+
+    /// Console connected to the stdin and stdout of the host
+    pub fn stdio() -> Self {
+        Self::new(std::io::stdin(), std::io::stdout())
+    }
+}
+
+impl Device for Console {
+    fn read(&mut self, offset: u16) -> u8 {
+        match offset {
+            0 => {
+                let mut byte = [0];
+                match self.input.read(&mut byte) {
+                    Ok(1) => byte[0],
+                    _ => {
+                        self.eof = true;
+                        0
+                    }
+                }
+            }
+            1 => self.eof as u8,
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        if offset == 0 {
+            // The guest has no way to handle host errors
+            let _ = self.output.write_all(&[value]);
+            let _ = self.output.flush();
+        }
+    }
+}
+
+/// Raises an interrupt every `interval` instructions while enabled.
+///
+/// * `0-1` - Interval in instructions, little endian
+/// * `2` - Control, bit 0 enables the timer
+/// * `3-4` - Instructions since the last interrupt, little endian
+pub struct Timer {
+    line: u8,
+    interval: u16,
+    enabled: bool,
+    count: u16,
+}
+
+impl Timer {
+    /// None if `line` isn't one of the [INTERRUPT_LINES]
+    pub fn new(line: u8) -> Option<Self> {
+        if line >= INTERRUPT_LINES {
+            return None;
+        }
+
+        Some(Self {
+            line,
+            interval: 0,
+            enabled: false,
+            count: 0,
+        })
+    }
+}
+
+impl Device for Timer {
+    fn read(&mut self, offset: u16) -> u8 {
+        let [interval_lo, interval_hi] = self.interval.to_le_bytes();
+        let [count_lo, count_hi] = self.count.to_le_bytes();
+        match offset {
+            0 => interval_lo,
+            1 => interval_hi,
+            2 => self.enabled as u8,
+            3 => count_lo,
+            4 => count_hi,
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        let [lo, hi] = self.interval.to_le_bytes();
+        match offset {
+            0 => self.interval = u16::from_le_bytes([value, hi]),
+            1 => self.interval = u16::from_le_bytes([lo, value]),
+            2 => {
+                self.enabled = value & 0b1 != 0;
+                self.count = 0;
+            }
+            _ => {}
+        }
+    }
+
+    fn tick(&mut self) -> Option<u8> {
+        if !self.enabled || self.interval == 0 {
+            return None;
+        }
+
+        self.count += 1;
+        if self.count < self.interval {
+            return None;
+        }
+
+        self.count = 0;
+        Some(self.line)
+    }
+}
+
+/// Pseudo random number generator, every read of offset `0` returns the next byte
+pub struct Random {
+    state: u32,
+}
+
+impl Random {
+    pub fn new(seed: u32) -> Self {
+        // Xorshift never leaves the zero state
+        let state = if seed == 0 { 0x2545_f491 } else { seed };
+        Self { state }
+    }
+
+    /// Seeded from the current time of the host
+    pub fn from_time() -> Self {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|time| time.subsec_nanos())
+            .unwrap_or_default();
+        Self::new(nanos)
+    }
+
+    fn next(&mut self) -> u32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        self.state
+    }
+}
+
+impl Device for Random {
+    fn read(&mut self, offset: u16) -> u8 {
+        match offset {
+            0 => (self.next() >> 24) as u8,
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, _offset: u16, _value: u8) {}
+}
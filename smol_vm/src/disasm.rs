@@ -0,0 +1,488 @@
+use smol_file::Symbol;
+
+/// Decoded instruction operand
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    /// Register with its 4-bit encoding
+    Register(u8),
+    /// 8-bit or 16-bit immediate value
+    Immediate(u16),
+    /// Memory address
+    Address(u16),
+    /// Memory address computed from the base register
+    Indirect { base: u8, offset: Offset },
+    /// Branch or call target in the instructions
+    Target(u16),
+}
+
+/// Offset added to the base register of an [Operand::Indirect] address
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Offset {
+    /// `[base]`
+    None,
+    /// `[base + index]` with the 4-bit register encoding of the index
+    Register(u8),
+    /// `[base + imm]`
+    Add(u8),
+    /// `[base - imm]`
+    Sub(u8),
+}
+
+/// Single decoded instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    /// Address of the opcode in the instructions
+    pub address: u16,
+    /// Raw bytes of the instruction, opcode included
+    pub bytes: Vec<u8>,
+    /// Mnemonic used by the assembler, or `.byte` for bytes that don't decode.
+    /// Stack pushes and pops have mnemonics that only the disassembler knows.
+    pub mnemonic: &'static str,
+    pub operands: Vec<Operand>,
+}
+
+impl Instruction {
+    /// Branch or call target of the instruction
+    pub fn target(&self) -> Option<u16> {
+        self.operands.iter().find_map(|op| match op {
+            Operand::Target(addr) => Some(*addr),
+            _ => None,
+        })
+    }
+}
+
+/// Name of the register in the 4-bit register encoding
+pub fn register_name(reg: u8) -> &'static str {
+    match reg & 0b1111 {
+        0b0000 => "r0",
+        0b0001 => "r1",
+        0b0010 => "r2",
+        0b0011 => "r3",
+        0b0100 => "r4",
+        0b0101 => "r5",
+        0b0110 => "r6",
+        0b0111 => "r7",
+        0b1000 => "vp",
+        0b1001 => "l0",
+        0b1010 => "l1",
+        0b1011 => "ic",
+        0b1100 => "fg",
+        0b1101 => "cr",
+        0b1110 => "sp",
+        0b1111 => "zr",
+        _ => unreachable!(),
+    }
+}
+
+/// Operand bytes after the opcode, None if the instruction is truncated
+struct Operands<'a> {
+    bytes: &'a [u8],
+}
+
+impl Operands<'_> {
+    fn u8(&self, idx: usize) -> Option<u8> {
+        self.bytes.get(idx).copied()
+    }
+
+    fn u16(&self, idx: usize) -> Option<u16> {
+        Some(u16::from_le_bytes([self.u8(idx)?, self.u8(idx + 1)?]))
+    }
+}
+
+type Decoded = (&'static str, Vec<Operand>, usize);
+
+/// Memory operand in the first two operand bytes, indirect if the low bit of the opcode is set
+fn decode_address(instr: u8, args: &Operands) -> Option<Operand> {
+    if instr & 0b1 == 0 {
+        return Some(Operand::Address(args.u16(0)?));
+    }
+
+    let desc = args.u8(0)?;
+    let arg = args.u8(1)?;
+    let offset = match desc >> 4 {
+        0b0000 => Offset::None,
+        0b0001 => Offset::Register(arg & 0b1111),
+        0b0010 => Offset::Add(arg),
+        0b0011 => Offset::Sub(arg),
+        _ => return None,
+    };
+
+    Some(Operand::Indirect {
+        base: desc & 0b1111,
+        offset,
+    })
+}
+
+fn decode_alu(instr: u8, args: &Operands) -> Option<Decoded> {
+    let is_imm = instr & 0b100 != 0;
+    let is_16b = instr & 0b10 != 0;
+    let regs = args.u8(0)?;
+    let dst = Operand::Register(regs & 0b1111);
+    let src = Operand::Register(regs >> 4);
+
+    if instr & 0b1 != 0 {
+        return decode_extended_alu(instr, args);
+    }
+
+    let (mnemonic, operands, len) = match ((instr >> 3) & 0b111, is_imm) {
+        // The VM reads the unused immediate of `not`, so it has to be skipped
+        (0b101, false) => ("not", vec![dst], 2),
+        (0b101, true) if is_16b => {
+            args.u16(1)?;
+            ("not", vec![dst], 4)
+        }
+        (0b101, true) => {
+            args.u8(1)?;
+            ("not", vec![dst], 3)
+        }
+        (0b111, false) => ("inc", vec![dst], 2),
+        (0b111, true) => ("dec", vec![dst], 2),
+        (op, false) => {
+            let name = ["add", "sub", "and", "or", "xor", "", "eqr"][op as usize];
+            (name, vec![dst, src], 2)
+        }
+        (op, true) => {
+            let name = ["addi", "subi", "andi", "ori", "xori", "", "eqi"][op as usize];
+            if is_16b {
+                (name, vec![dst, Operand::Immediate(args.u16(1)?)], 4)
+            } else {
+                (name, vec![dst, Operand::Immediate(args.u8(1)?.into())], 3)
+            }
+        }
+    };
+
+    let mnemonic = match (is_16b, mnemonic) {
+        (false, name) => name,
+        (true, "add") => "addl",
+        (true, "sub") => "subl",
+        (true, "and") => "andl",
+        (true, "or") => "orl",
+        (true, "xor") => "xorl",
+        (true, "eqr") => "eqrl",
+        (true, "addi") => "addil",
+        (true, "subi") => "subil",
+        (true, "andi") => "andil",
+        (true, "ori") => "oril",
+        (true, "xori") => "xoril",
+        (true, "eqi") => "eqil",
+        (true, "not") => "notl",
+        (true, "inc") => "incl",
+        (true, "dec") => "decl",
+        _ => unreachable!(),
+    };
+
+    Some((mnemonic, operands, len))
+}
+
+/// Operations selected by the noop bit of the ALU opcode
+fn decode_extended_alu(instr: u8, args: &Operands) -> Option<Decoded> {
+    let is_imm = instr & 0b100 != 0;
+    let is_16b = instr & 0b10 != 0;
+    let regs = args.u8(0)?;
+    let dst = Operand::Register(regs & 0b1111);
+
+    let names = match (instr >> 3) & 0b111 {
+        0b000 => ["mul", "muli", "mull", "mulil"],
+        0b001 => ["div", "divi", "divl", "divil"],
+        0b010 => ["mod", "modi", "modl", "modil"],
+        0b011 => ["shl", "shli", "shll", "shlil"],
+        0b100 => ["shr", "shri", "shrl", "shril"],
+        0b101 => ["sar", "sari", "sarl", "saril"],
+        _ => return None,
+    };
+    let mnemonic = names[is_imm as usize + 2 * is_16b as usize];
+
+    let decoded = match (is_imm, is_16b) {
+        (false, _) => (mnemonic, vec![dst, Operand::Register(regs >> 4)], 2),
+        (true, false) => (
+            mnemonic,
+            vec![dst, Operand::Immediate(args.u8(1)?.into())],
+            3,
+        ),
+        (true, true) => (mnemonic, vec![dst, Operand::Immediate(args.u16(1)?)], 4),
+    };
+
+    Some(decoded)
+}
+
+fn decode_load_store(instr: u8, args: &Operands) -> Option<Decoded> {
+    match (instr >> 4) & 0b11 {
+        // Store
+        0b00 => {
+            let has_memory_target = (instr >> 3) & 0b1 == 1;
+            if !has_memory_target && instr & 0b1 != 0 {
+                return None;
+            }
+            let decoded = match ((instr >> 1) & 0b11, has_memory_target) {
+                (src @ (0b00 | 0b01), false) => {
+                    let regs = args.u8(0)?;
+                    let name = if src == 0b00 { "st" } else { "stl" };
+                    let ops = vec![
+                        Operand::Register(regs & 0b1111),
+                        Operand::Register(regs >> 4),
+                    ];
+                    (name, ops, 2)
+                }
+                (0b10, false) => {
+                    let reg = Operand::Register(args.u8(0)? & 0b1111);
+                    ("sti", vec![reg, Operand::Immediate(args.u8(1)?.into())], 3)
+                }
+                (0b11, false) => {
+                    let reg = Operand::Register(args.u8(0)? & 0b1111);
+                    ("stil", vec![reg, Operand::Immediate(args.u16(1)?)], 4)
+                }
+                (src @ (0b00 | 0b01), true) => {
+                    let name = if src == 0b00 { "str" } else { "strl" };
+                    let addr = decode_address(instr, args)?;
+                    let reg = Operand::Register(args.u8(2)? & 0b1111);
+                    (name, vec![addr, reg], 4)
+                }
+                (0b10, true) => {
+                    let addr = decode_address(instr, args)?;
+                    ("stm", vec![addr, Operand::Immediate(args.u8(2)?.into())], 4)
+                }
+                (0b11, true) => {
+                    let addr = decode_address(instr, args)?;
+                    ("stml", vec![addr, Operand::Immediate(args.u16(2)?)], 5)
+                }
+                _ => unreachable!(),
+            };
+            Some(decoded)
+        }
+        // Load
+        0b01 => {
+            let name = match instr & 0b1110 {
+                0b1000 => "ldm",
+                0b1010 => "ldml",
+                _ => return None,
+            };
+            let addr = decode_address(instr, args)?;
+            let reg = Operand::Register(args.u8(2)? & 0b1111);
+            Some((name, vec![addr, reg], 4))
+        }
+        // Swap
+        0b11 => {
+            let decoded = match instr & 0b1111 {
+                0b0000 | 0b0010 => {
+                    let regs = args.u8(0)?;
+                    let name = if instr & 0b10 == 0 { "swp" } else { "swpl" };
+                    let ops = vec![
+                        Operand::Register(regs & 0b1111),
+                        Operand::Register(regs >> 4),
+                    ];
+                    (name, ops, 2)
+                }
+                0b1000..=0b1011 => {
+                    let name = if instr & 0b10 == 0 { "swpm" } else { "swpml" };
+                    let addr = decode_address(instr, args)?;
+                    let reg = Operand::Register(args.u8(2)? & 0b1111);
+                    (name, vec![addr, reg], 4)
+                }
+                _ => return None,
+            };
+            Some(decoded)
+        }
+        _ => None,
+    }
+}
+
+/// The assembler has no push and pop instructions, so `push`, `pushl`, `pushi`, `pushil`,
+/// `pop` and `popl` are only used by the disassembler and can't be assembled again.
+/// The same goes for `sv` with a register or a number instead of a variable name.
+fn decode_stack(instr: u8, args: &Operands) -> Option<Decoded> {
+    let decoded = match (instr >> 4) & 0b11 {
+        // Push
+        0b00 => match (instr >> 2) & 0b11 {
+            0b00 => ("push", vec![Operand::Register(args.u8(0)? & 0b1111)], 2),
+            0b01 => ("pushl", vec![Operand::Register(args.u8(0)? & 0b1111)], 2),
+            0b10 => ("pushi", vec![Operand::Immediate(args.u8(0)?.into())], 2),
+            0b11 => ("pushil", vec![Operand::Immediate(args.u16(0)?)], 3),
+            _ => unreachable!(),
+        },
+        // Pop
+        0b01 => match (instr >> 2) & 0b1 {
+            0b0 => ("pop", vec![Operand::Register(args.u8(0)? & 0b1111)], 2),
+            _ => ("popl", vec![Operand::Register(args.u8(0)? & 0b1111)], 2),
+        },
+        // Load variable
+        0b10 => match (instr >> 2) & 0b11 {
+            0b00 | 0b01 => ("sv", vec![Operand::Register(args.u8(0)? & 0b1111)], 2),
+            0b10 => ("sv", vec![Operand::Immediate(args.u8(0)?.into())], 2),
+            0b11 => ("sv", vec![Operand::Immediate(args.u16(0)?)], 3),
+            _ => unreachable!(),
+        },
+        // Unload variable
+        0b11 => ("uv", vec![], 1),
+        _ => unreachable!(),
+    };
+
+    Some(decoded)
+}
+
+fn decode_branch(instr: u8, args: &Operands) -> Option<Decoded> {
+    let op = (instr >> 3) & 0b111;
+    // Register-indirect jump and call
+    if matches!(op, 0b000 | 0b101) && instr & 0b111 == 0b001 {
+        let name = if op == 0b000 { "jmp" } else { "call" };
+        return Some((name, vec![Operand::Register(args.u8(0)? & 0b1111)], 2));
+    }
+
+    let name = match op {
+        0b000 if instr & 0b111 == 0b000 => "jmp",
+        0b000 => return None,
+        // Branch if flag set, the low bits select the flag
+        0b001 => match instr & 0b111 {
+            0b000 => "be",
+            0b001 => "bz",
+            0b010 => "bc",
+            0b011 => "bo",
+            0b100 => "bs",
+            0b101 => "berr",
+            _ => return None,
+        },
+        // Branch if flag not set
+        0b010 => match instr & 0b111 {
+            0b000 => "bne",
+            0b001 => "bnz",
+            0b010 => "bnc",
+            0b011 => "bno",
+            0b100 => "bns",
+            0b101 => "bnerr",
+            _ => return None,
+        },
+        0b011 => "bgt",
+        0b100 => "blt",
+        0b101 if instr & 0b111 == 0b111 => return Some(("syscall", vec![], 1)),
+        0b101 if instr & 0b111 == 0b000 => "call",
+        0b101 => return None,
+        0b110 => return Some(("ret", vec![], 1)),
+        0b111 => match instr & 0b111 {
+            0b000 => return Some(("reti", vec![], 1)),
+            0b001 => return Some(("ei", vec![], 1)),
+            0b010 => return Some(("di", vec![], 1)),
+            _ => return None,
+        },
+        _ => unreachable!(),
+    };
+
+    Some((name, vec![Operand::Target(args.u16(0)?)], 3))
+}
+
+/// Decode the instruction at `address`.
+/// Bytes that don't decode into an instruction become a single `.byte`.
+pub fn decode(instructions: &[u8], address: u16) -> Instruction {
+    let start = address as usize;
+    let instr = instructions[start];
+    let args = Operands {
+        bytes: &instructions[start + 1..],
+    };
+
+    let decoded = match (instr >> 6) & 0b11 {
+        0b00 => decode_alu(instr, &args),
+        0b01 => decode_load_store(instr, &args),
+        0b10 => decode_stack(instr, &args),
+        0b11 => decode_branch(instr, &args),
+        _ => unreachable!(),
+    };
+
+    let (mnemonic, operands, len) =
+        decoded.unwrap_or((".byte", vec![Operand::Immediate(instr.into())], 1));
+
+    Instruction {
+        address,
+        bytes: instructions[start..start + len].into(),
+        mnemonic,
+        operands,
+    }
+}
+
+/// Decode all of the instructions from start to finish
+pub fn disassemble(instructions: &[u8]) -> Vec<Instruction> {
+    let mut decoded: Vec<Instruction> = Vec::new();
+    let mut address = 0;
+    while address < instructions.len() {
+        let instr = decode(instructions, address as u16);
+        address += instr.bytes.len();
+        decoded.push(instr);
+    }
+
+    decoded
+}
+
+/// Add `main` and a generated label for every branch and call target without a symbol
+pub fn rebuild_labels(instrs: &[Instruction], main_start: u16, symbols: &[Symbol]) -> Vec<Symbol> {
+    let mut labels: Vec<Symbol> = symbols.to_vec();
+    if !labels.iter().any(|sym| sym.address == main_start) {
+        labels.push(Symbol {
+            name: "main".into(),
+            address: main_start,
+        });
+    }
+
+    for target in instrs.iter().filter_map(Instruction::target) {
+        if !labels.iter().any(|sym| sym.address == target) {
+            labels.push(Symbol {
+                name: format!("L_{target:04x}"),
+                address: target,
+            });
+        }
+    }
+
+    labels.sort_by_key(|sym| sym.address);
+    labels
+}
+
+/// Format the instruction with the assembler syntax, targets are replaced with labels
+pub fn format_instruction(instr: &Instruction, labels: &[Symbol]) -> String {
+    let operands: Vec<String> = instr
+        .operands
+        .iter()
+        .map(|op| match op {
+            Operand::Register(reg) => register_name(*reg).into(),
+            Operand::Immediate(value) => value.to_string(),
+            Operand::Address(addr) => addr.to_string(),
+            Operand::Indirect { base, offset } => {
+                let base = register_name(*base);
+                match offset {
+                    Offset::None => format!("[{base}]"),
+                    Offset::Register(index) => format!("[{base} + {}]", register_name(*index)),
+                    Offset::Add(imm) => format!("[{base} + {imm}]"),
+                    Offset::Sub(imm) => format!("[{base} - {imm}]"),
+                }
+            }
+            Operand::Target(addr) => match labels.iter().find(|sym| sym.address == *addr) {
+                Some(sym) => sym.name.clone(),
+                None => addr.to_string(),
+            },
+        })
+        .collect();
+
+    if operands.is_empty() {
+        instr.mnemonic.into()
+    } else {
+        format!("{} {}", instr.mnemonic, operands.join(" "))
+    }
+}
+
+/// Listing of the instructions with addresses, raw bytes and labels
+pub fn listing(instructions: &[u8], main_start: u16, symbols: &[Symbol]) -> String {
+    let instrs = disassemble(instructions);
+    let labels = rebuild_labels(&instrs, main_start, symbols);
+
+    let mut out = String::new();
+    for instr in &instrs {
+        for label in labels.iter().filter(|sym| sym.address == instr.address) {
+            out.push_str(&format!("{}:\n", label.name));
+        }
+
+        let bytes: Vec<String> = instr.bytes.iter().map(|b| format!("{b:02x}")).collect();
+        out.push_str(&format!(
+            "    {:04x}: {:<15} {}\n",
+            instr.address,
+            bytes.join(" "),
+            format_instruction(instr, &labels)
+        ));
+    }
+
+    out
+}
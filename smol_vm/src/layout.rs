@@ -0,0 +1,66 @@
+use std::ops::Range;
+
+pub use smol_file::memory::MEMORY_SIZE;
+use smol_file::memory::{GUARD_SIZE, IO_START, STACK_SIZE};
+
+/// Placement of the stack and variable segments in the memory.
+///
+/// The stack starts from address 0 and grows up to `stack_size`.
+/// It's followed by the guard region, which faults on every access,
+/// and the variable segment that goes up to the device registers at [IO_START].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layout {
+    stack_size: u16,
+    guard_size: u16,
+}
+
+impl Layout {
+    /// None if the stack and the guard region reach [IO_START] and leave no space for the variables
+    pub fn new(stack_size: u16, guard_size: u16) -> Option<Self> {
+        if stack_size.checked_add(guard_size)? >= IO_START {
+            return None;
+        }
+        Some(Self {
+            stack_size,
+            guard_size,
+        })
+    }
+
+    pub fn stack_size(&self) -> u16 {
+        self.stack_size
+    }
+
+    pub fn guard_size(&self) -> u16 {
+        self.guard_size
+    }
+
+    /// Addresses of the stack segment
+    pub fn stack(&self) -> Range<usize> {
+        0..self.stack_size as usize
+    }
+
+    /// Addresses between the stack and the variables
+    pub fn guard(&self) -> Range<usize> {
+        self.stack_size as usize..self.variable_start() as usize
+    }
+
+    /// Addresses of the variable segment, storage has to fit into it
+    pub fn variables(&self) -> Range<usize> {
+        self.variable_start() as usize..IO_START as usize
+    }
+
+    /// Base address of the variables, storage offsets are relative to it
+    pub fn variable_start(&self) -> u16 {
+        self.stack_size + self.guard_size
+    }
+}
+
+impl Default for Layout {
+    /// Segments of the default memory map in [smol_file::memory]
+    fn default() -> Self {
+        Self {
+            stack_size: STACK_SIZE,
+            guard_size: GUARD_SIZE,
+        }
+    }
+}
@@ -5,11 +5,23 @@ use std::ops::{
     SubAssign,
 };
 
+pub mod debugger;
+pub mod device;
+pub mod disasm;
+pub mod layout;
 mod registers;
 pub mod syscall;
 
-use registers::Registers;
-use syscall::vm_syscall;
+use device::{Bus, Console, Random, Timer, CONSOLE_BASE, RANDOM_BASE, TIMER_BASE, TIMER_LINE};
+use layout::{Layout, MEMORY_SIZE};
+use registers::{
+    FG_ARITHMETIC, FG_CARRY, FG_COMPARE, FG_EQUAL, FG_GREATER, FG_LESS, FG_OVERFLOW, FG_SIGN,
+    FG_ZERO,
+};
+use smol_file::SmolFile;
+use syscall::{SyscallError, SyscallHandler, SyscallOutcome};
+
+pub use registers::{Registers, FG_ERROR, FG_INTERRUPT};
 
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 enum Either<L, R> {
@@ -33,6 +45,18 @@ impl RegEither {
             Self::Right(v) => v,
         }
     }
+
+    fn is_zero(self) -> bool {
+        self.as_u16() == 0
+    }
+
+    /// Most significant bit of the value's width is set
+    fn is_negative(self) -> bool {
+        match self {
+            Self::Left(v) => v & 0x80 != 0,
+            Self::Right(v) => v & 0x8000 != 0,
+        }
+    }
 }
 
 impl From<u8> for RegEither {
@@ -48,6 +72,12 @@ impl From<u16> for RegEither {
 }
 
 macro_rules! either_oper {
+    ($lvalue:expr, $rvalue:expr, fn $op:ident) => {
+        match $lvalue {
+            Either::Left(value) => Either::Left(value.$op($rvalue.as_u8())),
+            Either::Right(value) => Either::Right(value.$op($rvalue.as_u16())),
+        }
+    };
     ($lvalue:expr, $rvalue:expr, $op:tt) => {
         match $lvalue {
             Either::Left(value) => Either::Left(value $op $rvalue.as_u8()),
@@ -56,11 +86,87 @@ macro_rules! either_oper {
     };
 }
 
+macro_rules! either_overflowing {
+    ($lvalue:expr, $rvalue:expr, $op:ident) => {
+        match $lvalue {
+            Either::Left(value) => {
+                let (result, carry) = value.$op($rvalue.as_u8());
+                let (_, overflow) = (value as i8).$op($rvalue.as_u8() as i8);
+                (Either::Left(result), carry, overflow)
+            }
+            Either::Right(value) => {
+                let (result, carry) = value.$op($rvalue.as_u16());
+                let (_, overflow) = (value as i16).$op($rvalue.as_u16() as i16);
+                (Either::Right(result), carry, overflow)
+            }
+        }
+    };
+}
+
+impl RegEither {
+    fn wrapping_mul(self, rhs: Self) -> Self {
+        either_oper!(self, rhs, fn wrapping_mul)
+    }
+
+    /// Unsigned division, None if `rhs` is zero
+    fn checked_div(self, rhs: Self) -> Option<Self> {
+        (!rhs.is_zero()).then(|| either_oper!(self, rhs, /))
+    }
+
+    /// Unsigned remainder, None if `rhs` is zero
+    fn checked_rem(self, rhs: Self) -> Option<Self> {
+        (!rhs.is_zero()).then(|| either_oper!(self, rhs, %))
+    }
+
+    /// Logical shift left, shifting by the width or more gives 0
+    fn shl(self, rhs: Self) -> Self {
+        let amount = rhs.as_u16().into();
+        match self {
+            Self::Left(v) => Self::Left(v.checked_shl(amount).unwrap_or(0)),
+            Self::Right(v) => Self::Right(v.checked_shl(amount).unwrap_or(0)),
+        }
+    }
+
+    /// Logical shift right, shifting by the width or more gives 0
+    fn shr(self, rhs: Self) -> Self {
+        let amount = rhs.as_u16().into();
+        match self {
+            Self::Left(v) => Self::Left(v.checked_shr(amount).unwrap_or(0)),
+            Self::Right(v) => Self::Right(v.checked_shr(amount).unwrap_or(0)),
+        }
+    }
+
+    /// Arithmetic shift right, shifting by the width or more fills with the sign
+    fn sar(self, rhs: Self) -> Self {
+        let amount = rhs.as_u16().into();
+        match self {
+            Self::Left(v) => {
+                let v = v as i8;
+                Self::Left(v.checked_shr(amount).unwrap_or(v >> 7) as u8)
+            }
+            Self::Right(v) => {
+                let v = v as i16;
+                Self::Right(v.checked_shr(amount).unwrap_or(v >> 15) as u16)
+            }
+        }
+    }
+
+    /// Wrapping add, with the unsigned carry and the signed overflow
+    fn overflowing_add(self, rhs: Self) -> (Self, bool, bool) {
+        either_overflowing!(self, rhs, overflowing_add)
+    }
+
+    /// Wrapping subtract, with the unsigned borrow and the signed overflow
+    fn overflowing_sub(self, rhs: Self) -> (Self, bool, bool) {
+        either_overflowing!(self, rhs, overflowing_sub)
+    }
+}
+
 impl Add for RegEither {
     type Output = RegEither;
 
     fn add(self, rhs: Self) -> Self::Output {
-        either_oper!(self, rhs, +)
+        either_oper!(self, rhs, fn wrapping_add)
     }
 }
 
@@ -68,7 +174,7 @@ impl Sub for RegEither {
     type Output = RegEither;
 
     fn sub(self, rhs: Self) -> Self::Output {
-        either_oper!(self, rhs, -)
+        either_oper!(self, rhs, fn wrapping_sub)
     }
 }
 
@@ -169,14 +275,184 @@ impl RegisterValue {
     }
 }
 
+/// Reason why the [Vm] stopped running
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitReason {
+    /// Instruction counter reached the end of the instructions
+    Finished,
+    /// [Vm::run_for] executed the requested amount of instructions
+    InstructionLimit,
+    /// [Vm::run_until] predicate returned true
+    Predicate,
+    /// Guest called exit with the status
+    Exited(i32),
+}
+
+/// Faults that stop the [Vm].
+/// `ic` and `opcode` point to the instruction that caused the fault.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VmError {
+    /// Opcode doesn't decode into any instruction
+    InvalidOpcode { ic: u16, opcode: u8 },
+    /// Opcode is reserved but not implemented yet
+    Unimplemented { ic: u16, opcode: u8 },
+    /// Instruction, or one of its operands, is outside of the instructions.
+    /// `opcode` is `None` if `ic` itself is out of range.
+    InstructionOutOfRange { ic: u16, opcode: Option<u8> },
+    /// Memory access outside of the memory
+    MemoryOutOfRange { ic: u16, opcode: u8, address: u16 },
+    /// Memory access to the guard region between the stack and the variables
+    GuardRegion { ic: u16, opcode: u8, address: u16 },
+    /// Push past the end of the memory
+    StackOverflow { ic: u16, opcode: u8 },
+    /// Pop from an empty stack
+    StackUnderflow { ic: u16, opcode: u8 },
+    /// System call id in r0 is not implemented
+    UnimplementedSyscall { ic: u16, opcode: u8, id: u8 },
+    /// Division or modulo with a zero divisor
+    DivisionByZero { ic: u16, opcode: u8 },
+    /// Device raised an interrupt line that doesn't exist after the instruction at `ic`
+    InvalidInterruptLine { ic: u16, line: u8 },
+}
+
+impl VmError {
+    /// Instruction counter of the faulting instruction
+    pub fn ic(&self) -> u16 {
+        match *self {
+            Self::InvalidOpcode { ic, .. }
+            | Self::Unimplemented { ic, .. }
+            | Self::InstructionOutOfRange { ic, .. }
+            | Self::MemoryOutOfRange { ic, .. }
+            | Self::GuardRegion { ic, .. }
+            | Self::StackOverflow { ic, .. }
+            | Self::StackUnderflow { ic, .. }
+            | Self::UnimplementedSyscall { ic, .. }
+            | Self::DivisionByZero { ic, .. }
+            | Self::InvalidInterruptLine { ic, .. } => ic,
+        }
+    }
+
+    /// Opcode of the faulting instruction, if `ic` pointed to one
+    pub fn opcode(&self) -> Option<u8> {
+        match *self {
+            Self::InstructionOutOfRange { opcode, .. } => opcode,
+            Self::InvalidInterruptLine { .. } => None,
+            Self::InvalidOpcode { opcode, .. }
+            | Self::Unimplemented { opcode, .. }
+            | Self::MemoryOutOfRange { opcode, .. }
+            | Self::GuardRegion { opcode, .. }
+            | Self::StackOverflow { opcode, .. }
+            | Self::StackUnderflow { opcode, .. }
+            | Self::UnimplementedSyscall { opcode, .. }
+            | Self::DivisionByZero { opcode, .. } => Some(opcode),
+        }
+    }
+}
+
+impl std::fmt::Display for VmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::InvalidOpcode { ic, opcode } => {
+                write!(f, "invalid opcode {opcode:#010b} at {ic}")
+            }
+            Self::Unimplemented { ic, opcode } => {
+                write!(f, "unimplemented instruction {opcode:#010b} at {ic}")
+            }
+            Self::InstructionOutOfRange { ic, opcode: None } => {
+                write!(f, "tried to access non-existing instruction {ic}")
+            }
+            Self::InstructionOutOfRange {
+                ic,
+                opcode: Some(opcode),
+            } => write!(
+                f,
+                "operands of instruction {opcode:#010b} at {ic} are out of range"
+            ),
+            Self::MemoryOutOfRange {
+                ic,
+                opcode,
+                address,
+            } => write!(
+                f,
+                "instruction {opcode:#010b} at {ic} accessed memory out of range at {address}"
+            ),
+            Self::GuardRegion {
+                ic,
+                opcode,
+                address,
+            } => write!(
+                f,
+                "instruction {opcode:#010b} at {ic} accessed the guard region at {address}"
+            ),
+            Self::StackOverflow { ic, opcode } => {
+                write!(f, "stack overflow in instruction {opcode:#010b} at {ic}")
+            }
+            Self::StackUnderflow { ic, opcode } => {
+                write!(f, "stack underflow in instruction {opcode:#010b} at {ic}")
+            }
+            Self::UnimplementedSyscall { ic, opcode, id } => write!(
+                f,
+                "system call {id} is not implemented (instruction {opcode:#010b} at {ic})"
+            ),
+            Self::DivisionByZero { ic, opcode } => {
+                write!(f, "division by zero in instruction {opcode:#010b} at {ic}")
+            }
+            Self::InvalidInterruptLine { ic, line } => {
+                write!(f, "device raised invalid interrupt line {line} after {ic}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for VmError {}
+
+/// Reasons why a file can't be loaded into the [Vm]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadError {
+    /// Storage item doesn't fit into the variable segment of the layout
+    StorageOutOfRange { item: u16 },
+    /// Initialised data of the storage item isn't as long as the item
+    InitDataMismatch { item: u16, size: u16, len: usize },
+}
+
+impl std::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::StorageOutOfRange { item } => write!(
+                f,
+                "storage item {item} doesn't fit into the variable segment"
+            ),
+            Self::InitDataMismatch { item, size, len } => write!(
+                f,
+                "storage item {item} is {size} bytes but has {len} bytes of data"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
 #[derive(Debug)]
 #[allow(dead_code)]
 pub struct Stack {
-    /// Stack size of 64kib since that's what the u16 stack pointer allows.
-    memory: [u8; u16::MAX as usize],
+    /// Memory of 64kib since that's what the u16 addresses allow.
+    memory: [u8; MEMORY_SIZE],
+    /// Segments of the memory
+    layout: Layout,
 }
 
 impl Stack {
+    pub fn with_layout(layout: Layout) -> Self {
+        Self {
+            memory: [0; MEMORY_SIZE],
+            layout,
+        }
+    }
+
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
     // Get the memory space from the stack pointer
     pub fn from_sp(&self, sp: u16) -> &[u8] {
         &self.memory[sp as usize..]
@@ -197,52 +473,51 @@ impl Stack {
         &mut self.memory
     }
 
-    /// Stack is the first 32kib bytes
+    /// Stack segment from the start of the memory
     pub fn stack(&self) -> &[u8] {
-        &self.memory[..(u16::MAX / 2) as usize]
+        &self.memory[self.layout.stack()]
     }
 
-    /// Stack is the first 32kib bytes
+    /// Stack segment from the start of the memory
     pub fn stack_mut(&mut self) -> &mut [u8] {
-        &mut self.memory[..(u16::MAX / 2) as usize]
+        &mut self.memory[self.layout.stack()]
     }
 
-    /// Variable space is the last 32kib bytes
+    /// Variable segment after the guard region
     pub fn variable(&self) -> &[u8] {
-        &self.memory[..(u16::MAX / 2) as usize]
+        &self.memory[self.layout.variables()]
     }
 
-    /// Variable space is the last 32kib bytes
+    /// Variable segment after the guard region
     pub fn variable_mut(&mut self) -> &mut [u8] {
-        &mut self.memory[(u16::MAX / 2) as usize..]
+        &mut self.memory[self.layout.variables()]
     }
 
     pub fn save_value(&mut self, addr: u16, value: u8) {
         self.memory[addr as usize] = value;
     }
 
-    pub fn save_value_16(&mut self, addr: u16, value: u16) {
-        let [li, mi] = value.to_le_bytes();
-        self.memory[addr as usize] = li;
-        self.memory[addr as usize + 1] = mi;
+    /// None if the value goes past the end of the memory, accesses don't wrap around
+    pub fn save_value_16(&mut self, addr: u16, value: u16) -> Option<()> {
+        let bytes = self.memory.get_mut(addr as usize..addr as usize + 2)?;
+        bytes.copy_from_slice(&value.to_le_bytes());
+        Some(())
     }
 
     pub fn load_value(&mut self, addr: u16) -> u8 {
         self.memory[addr as usize]
     }
 
-    pub fn load_value_16(&mut self, addr: u16) -> u16 {
-        let li = self.memory[addr as usize];
-        let mi = self.memory[addr as usize + 1];
-        u16::from_le_bytes([li, mi])
+    /// None if the value goes past the end of the memory, accesses don't wrap around
+    pub fn load_value_16(&mut self, addr: u16) -> Option<u16> {
+        let bytes = self.memory.get(addr as usize..addr as usize + 2)?;
+        Some(u16::from_le_bytes([bytes[0], bytes[1]]))
     }
 }
 
 impl Default for Stack {
     fn default() -> Self {
-        Self {
-            memory: [0; u16::MAX as usize],
-        }
+        Self::with_layout(Layout::default())
     }
 }
 
@@ -263,33 +538,215 @@ impl Instructions {
     }
 }
 
-#[derive(Debug, Default)]
+/// Start of the interrupt vector table in memory.
+/// Every line has a 16-bit handler address, line `n` is at `IVT_START + 2 * n`.
+pub use smol_file::memory::IVT_START;
+/// Number of interrupt lines
+pub const INTERRUPT_LINES: u8 = 8;
+
+#[derive(Debug)]
 #[allow(dead_code)]
 pub struct Vm {
     pub registers: Registers,
     pub stack: Stack,
     pub instructions: Instructions,
+    /// Devices mapped into the memory
+    pub bus: Bus,
+    /// Handler of the `syscall` instruction, the host system by default
+    pub syscalls: Box<dyn SyscallHandler>,
+    /// Bit `n` is set while interrupt line `n` is waiting to be handled
+    pending_interrupts: u8,
+    /// Set once the guest has exited, the VM doesn't execute after it
+    exit_status: Option<i32>,
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Self {
+            registers: Registers::default(),
+            stack: Stack::default(),
+            instructions: Instructions::default(),
+            bus: Bus::default(),
+            syscalls: syscall::host(),
+            pending_interrupts: 0,
+            exit_status: None,
+        }
+    }
 }
 
 impl Vm {
-    fn stack_pop(&mut self) -> u8 {
-        self.registers.sp -= 1;
-        self.stack.load_value(self.registers.sp)
+    /// Load the instructions and initialised variables of the file.
+    /// Nothing is loaded if the storage doesn't fit into the variable segment.
+    pub fn load_file(&mut self, file: SmolFile) -> Result<(), LoadError> {
+        // Storage offsets are relative to the variable segment
+        let variables_len = self.stack.variable().len();
+        for (item, storage) in file.storage.items.iter().enumerate() {
+            let item = item as u16;
+            let end = storage.offset as usize + storage.size as usize;
+            if end > variables_len {
+                return Err(LoadError::StorageOutOfRange { item });
+            }
+
+            match &storage.init_data {
+                Some(data) if data.len() != storage.size as usize => {
+                    return Err(LoadError::InitDataMismatch {
+                        item,
+                        size: storage.size,
+                        len: data.len(),
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        self.registers.ic = file.main_start;
+        self.registers.vp = self.stack.layout().variable_start();
+        self.instructions.instructions = file.instructions;
+        let variables = self.stack.variable_mut();
+        for storage in file.storage.items {
+            if let Some(data) = storage.init_data {
+                let start = storage.offset as usize;
+                variables[start..start + data.len()].copy_from_slice(&data);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Virtual machine with the given memory layout
+    pub fn with_layout(layout: Layout) -> Self {
+        Self {
+            stack: Stack::with_layout(layout),
+            ..Default::default()
+        }
+    }
+
+    /// Instruction counter and opcode of the instruction that is being executed
+    fn fault(&self) -> (u16, u8) {
+        let ic = self.registers.ic;
+        (ic, self.instructions.get(ic))
+    }
+
+    fn stack_pop(&mut self) -> Result<u8, VmError> {
+        let Some(sp) = self.registers.sp.checked_sub(1) else {
+            let (ic, opcode) = self.fault();
+            return Err(VmError::StackUnderflow { ic, opcode });
+        };
+        let value = self.load_value(sp)?;
+        self.registers.sp = sp;
+        Ok(value)
     }
 
-    fn stack_pop_16b(&mut self) -> u16 {
-        self.registers.sp -= 2;
-        self.stack.load_value_16(self.registers.sp)
+    fn stack_pop_16b(&mut self) -> Result<u16, VmError> {
+        let Some(sp) = self.registers.sp.checked_sub(2) else {
+            let (ic, opcode) = self.fault();
+            return Err(VmError::StackUnderflow { ic, opcode });
+        };
+        let value = self.load_value_16(sp)?;
+        self.registers.sp = sp;
+        Ok(value)
     }
 
-    fn stack_push(&mut self, val: u8) {
-        self.stack.save_value(self.registers.sp, val);
+    fn stack_push(&mut self, val: u8) -> Result<(), VmError> {
+        let sp = self.registers.sp;
+        if sp as usize + 1 > self.stack.layout().stack_size() as usize {
+            let (ic, opcode) = self.fault();
+            return Err(VmError::StackOverflow { ic, opcode });
+        }
+        self.stack.save_value(sp, val);
         self.registers.sp += 1;
+        Ok(())
     }
 
-    fn stack_push_16b(&mut self, val: u16) {
-        self.stack.save_value_16(self.registers.sp, val);
+    fn stack_push_16b(&mut self, val: u16) -> Result<(), VmError> {
+        let sp = self.registers.sp;
+        if sp as usize + 2 > self.stack.layout().stack_size() as usize {
+            let (ic, opcode) = self.fault();
+            return Err(VmError::StackOverflow { ic, opcode });
+        }
+        self.stack
+            .save_value_16(sp, val)
+            .expect("stack segment is inside of the memory");
         self.registers.sp += 2;
+        Ok(())
+    }
+
+    /// Make sure that `width` bytes from `addr` are inside of the memory.
+    /// Accesses don't wrap around, 16-bit access at `0xFFFF` is out of range.
+    fn check_memory(&self, addr: u16, width: usize) -> Result<(), VmError> {
+        let (ic, opcode) = self.fault();
+        let end = addr as usize + width;
+        if end > self.stack.memory().len() {
+            return Err(VmError::MemoryOutOfRange {
+                ic,
+                opcode,
+                address: addr,
+            });
+        }
+
+        let guard = self.stack.layout().guard();
+        if (addr as usize) < guard.end && guard.start < end {
+            return Err(VmError::GuardRegion {
+                ic,
+                opcode,
+                address: addr,
+            });
+        }
+        Ok(())
+    }
+
+    /// True if any byte of the 16-bit value at `addr` belongs to a device.
+    /// `addr` must be below `0xFFFF`.
+    fn is_mapped_16(&self, addr: u16) -> bool {
+        self.bus.is_mapped(addr) || self.bus.is_mapped(addr + 1)
+    }
+
+    fn save_value(&mut self, addr: u16, value: u8) -> Result<(), VmError> {
+        self.check_memory(addr, 1)?;
+        if let Some((device, offset)) = self.bus.device_at(addr) {
+            device.write(offset, value);
+            return Ok(());
+        }
+
+        self.stack.save_value(addr, value);
+        Ok(())
+    }
+
+    fn save_value_16(&mut self, addr: u16, value: u16) -> Result<(), VmError> {
+        self.check_memory(addr, 2)?;
+        if self.is_mapped_16(addr) {
+            let [li, mi] = value.to_le_bytes();
+            self.save_value(addr, li)?;
+            return self.save_value(addr + 1, mi);
+        }
+
+        self.stack
+            .save_value_16(addr, value)
+            .expect("checked by check_memory");
+        Ok(())
+    }
+
+    fn load_value(&mut self, addr: u16) -> Result<u8, VmError> {
+        self.check_memory(addr, 1)?;
+        if let Some((device, offset)) = self.bus.device_at(addr) {
+            return Ok(device.read(offset));
+        }
+
+        Ok(self.stack.load_value(addr))
+    }
+
+    fn load_value_16(&mut self, addr: u16) -> Result<u16, VmError> {
+        self.check_memory(addr, 2)?;
+        if self.is_mapped_16(addr) {
+            let li = self.load_value(addr)?;
+            let mi = self.load_value(addr + 1)?;
+            return Ok(u16::from_le_bytes([li, mi]));
+        }
+
+        Ok(self
+            .stack
+            .load_value_16(addr)
+            .expect("checked by check_memory"))
     }
 
     fn register_val(&self, reg: u8) -> RegisterValue {
@@ -335,16 +792,33 @@ impl Vm {
         }
     }
 
-    fn immediate_instr(&self, ic: u16) -> u8 {
-        self.instructions.instructions[ic as usize]
+    /// Get the instruction byte `offset` bytes after ic
+    fn immediate_instr(&self, offset: u16) -> Result<u8, VmError> {
+        let idx = self.registers.ic as usize + offset as usize;
+        match self.instructions.instructions.get(idx) {
+            Some(value) => Ok(*value),
+            None => {
+                let (ic, opcode) = self.fault();
+                Err(VmError::InstructionOutOfRange {
+                    ic,
+                    opcode: Some(opcode),
+                })
+            }
+        }
     }
 
-    /// Turn instructions from ic and ic+1 into u16
-    fn immediate_instr_16b(&self, ic: u16) -> u16 {
-        // TODO: make this faster with unsafe
-        let instrs = &self.instructions.instructions[ic as usize..];
+    /// Turn instructions from ic+offset and ic+offset+1 into u16
+    fn immediate_instr_16b(&self, offset: u16) -> Result<u16, VmError> {
         // The archicture is little endian so we need to create u16 from le bytes
-        u16::from_le_bytes([instrs[0], instrs[1]])
+        Ok(u16::from_le_bytes([
+            self.immediate_instr(offset)?,
+            self.immediate_instr(offset + 1)?,
+        ]))
+    }
+
+    fn invalid_opcode(&self) -> VmError {
+        let (ic, opcode) = self.fault();
+        VmError::InvalidOpcode { ic, opcode }
     }
 
     fn decode_register(&self, regs: u8) -> RegisterValue {
@@ -358,36 +832,110 @@ impl Vm {
         (self.register_val(r0), self.register_val(r1))
     }
 
-    fn decode_alu_instr(&mut self, instr: u8) -> u16 {
-        // TODO: 16 bit support
-        let (used, source_vals) = match instr & 0b100 {
-            0b000 => {
-                let regs = self.instructions.get(self.registers.ic + 1);
-                (2, self.decode_registers(regs))
+    /// Wrapping add or subtract that sets the zero, carry, overflow and sign flags
+    fn arithmetic(&mut self, lhs: RegEither, rhs: RegEither, subtract: bool) -> RegEither {
+        let (result, carry, overflow) = if subtract {
+            lhs.overflowing_sub(rhs)
+        } else {
+            lhs.overflowing_add(rhs)
+        };
+
+        self.registers.fg &= !FG_ARITHMETIC;
+        for (is_set, flag) in [
+            (result.is_zero(), FG_ZERO),
+            (carry, FG_CARRY),
+            (overflow, FG_OVERFLOW),
+            (result.is_negative(), FG_SIGN),
+        ] {
+            if is_set {
+                self.registers.fg |= flag;
             }
-            // TODO: Do something less hacky
-            0b100 if (instr >> 3) & 0b111 == 0b111 => {
-                let regs = self.instructions.get(self.registers.ic + 1);
-                (2, self.decode_registers(regs))
+        }
+
+        result
+    }
+
+    /// Operations selected by the noop bit, they don't change the flags
+    fn extended_alu(
+        &self,
+        instr: u8,
+        lhs: RegEither,
+        rhs: RegEither,
+    ) -> Result<RegEither, VmError> {
+        let division_by_zero = || {
+            let (ic, opcode) = self.fault();
+            VmError::DivisionByZero { ic, opcode }
+        };
+
+        match (instr >> 3) & 0b111 {
+            // Multiply
+            0b000 => Ok(lhs.wrapping_mul(rhs)),
+            // Divide
+            0b001 => lhs.checked_div(rhs).ok_or_else(division_by_zero),
+            // Modulo
+            0b010 => lhs.checked_rem(rhs).ok_or_else(division_by_zero),
+            // Shift left
+            0b011 => Ok(lhs.shl(rhs)),
+            // Logical shift right
+            0b100 => Ok(lhs.shr(rhs)),
+            // Arithmetic shift right
+            0b101 => Ok(lhs.sar(rhs)),
+            _ => Err(self.invalid_opcode()),
+        }
+    }
+
+    fn decode_alu_instr(&mut self, instr: u8) -> Result<u16, VmError> {
+        let is_16b = instr & 0b10 != 0;
+        // The noop bit selects the extended operations
+        let is_extended = instr & 0b1 != 0;
+        // Increment/decrement use the source bit to select the function
+        let is_immediate = instr & 0b100 != 0 && (is_extended || (instr >> 3) & 0b111 != 0b111);
+
+        let regs = self.immediate_instr(1)?;
+        let (mut target, mut source) = self.decode_registers(regs);
+        // TODO: Don't hackily ignore the second encoded register
+        let used = match (is_immediate, is_16b) {
+            (false, _) => 2,
+            (true, false) => {
+                source.value = self.immediate_instr(2)?.into();
+                3
             }
-            0b100 => {
-                // TODO: Don't hackily ignore the second encoded register
-                let regs = self.instructions.get(self.registers.ic + 1);
-                let value = self.instructions.get(self.registers.ic + 2);
-                let mut regs = self.decode_registers(regs);
-                regs.1.value = value.into();
-                (3, regs)
+            (true, true) => {
+                source.value = self.immediate_instr_16b(2)?.into();
+                4
             }
-            _ => unreachable!(),
         };
 
-        let mut source_vals = source_vals;
+        // Operate with the width of the instruction, not the width of the registers
+        let width = |value: RegEither| -> RegEither {
+            if is_16b {
+                value.as_u16().into()
+            } else {
+                value.as_u8().into()
+            }
+        };
+        target.value = width(target.value);
+        source.value = width(source.value);
+        let mut source_vals = (target, source);
+
+        if is_extended {
+            source_vals.0.value =
+                self.extended_alu(instr, source_vals.0.value, source_vals.1.value)?;
+            self.register_save(source_vals.0);
+            return Ok(used);
+        }
 
         match (instr >> 3) & 0b111 {
             // Add
-            0b000 => source_vals.0.value += source_vals.1.value,
+            0b000 => {
+                source_vals.0.value =
+                    self.arithmetic(source_vals.0.value, source_vals.1.value, false)
+            }
             // Subtract
-            0b001 => source_vals.0.value -= source_vals.1.value,
+            0b001 => {
+                source_vals.0.value =
+                    self.arithmetic(source_vals.0.value, source_vals.1.value, true)
+            }
             // Binary and
             0b010 => source_vals.0.value &= source_vals.1.value,
             // Binary or
@@ -399,46 +947,77 @@ impl Vm {
             // Equality
             0b110 => {
                 // reset equailty flags
-                self.registers.fg &= 0b000;
+                self.registers.fg &= !FG_COMPARE;
                 if source_vals.0.value == source_vals.1.value {
-                    self.registers.fg |= 0b1;
+                    self.registers.fg |= FG_EQUAL;
                 } else if source_vals.0.value > source_vals.1.value {
-                    self.registers.fg |= 0b10;
+                    self.registers.fg |= FG_GREATER;
                 } else if source_vals.0.value < source_vals.1.value {
-                    self.registers.fg |= 0b100;
+                    self.registers.fg |= FG_LESS;
                 }
+                // Equality only sets the flags
+                return Ok(used);
             }
             0b111 => {
                 // Decode the increment/decrement function
-                source_vals.0.value = match instr & 0b100 {
-                    // Increment
-                    0b000 => source_vals.0.value + (1_u8).into(),
-                    // Decrement
-                    0b100 => source_vals.0.value - (1_u8).into(),
+                let is_decrement = match instr & 0b100 {
+                    0b000 => false,
+                    0b100 => true,
                     _ => unreachable!("This statement should be literally impossible"),
-                }
+                };
+                source_vals.0.value =
+                    self.arithmetic(source_vals.0.value, (1_u8).into(), is_decrement)
             }
 
-            // Since we use and (&) we limit ourself to values 0-3
-            _ => unimplemented!("Only Add AluFamily is implemnted"),
+            // Since we use and (&) we limit ourself to values 0-7
+            _ => unreachable!(),
         }
         self.register_save(source_vals.0);
 
-        used
+        Ok(used)
+    }
+
+    /// Address of the memory operand in the two bytes after the opcode.
+    /// If the low bit of the opcode is set, the bytes describe an indirect address:
+    /// the mode and the base register in the first byte, the index register or
+    /// an 8-bit immediate in the second byte.
+    fn memory_address(&self, instr: u8) -> Result<u16, VmError> {
+        if instr & 0b1 == 0 {
+            // Absolute address is encoded the same way immediates are
+            return self.immediate_instr_16b(1);
+        }
+
+        let desc = self.immediate_instr(1)?;
+        let arg = self.immediate_instr(2)?;
+        let base = self.decode_register(desc).value.as_u16();
+        match desc >> 4 {
+            // [base]
+            0b0000 => Ok(base),
+            // [base + index]
+            0b0001 => Ok(base.wrapping_add(self.decode_register(arg).value.as_u16())),
+            // [base + imm]
+            0b0010 => Ok(base.wrapping_add(arg.into())),
+            // [base - imm]
+            0b0011 => Ok(base.wrapping_sub(arg.into())),
+            _ => Err(self.invalid_opcode()),
+        }
     }
 
-    fn decode_load_store_instr(&mut self, instr: u8) -> u16 {
+    fn decode_load_store_instr(&mut self, instr: u8) -> Result<u16, VmError> {
         let mut used: u16 = 1;
 
-        let ic = self.registers.ic + 1;
         match (instr >> 4) & 0b11 {
             // Store
             0b00 => {
                 let has_memory_target = (instr >> 3) & 0b1 == 1;
+                // Only memory targets have an indirect form
+                if !has_memory_target && instr & 0b1 != 0 {
+                    return Err(self.invalid_opcode());
+                }
 
                 // If the target is memory, the source value can be decoded
                 // after the two addrees bytes
-                let src_ic = if has_memory_target { ic + 1 } else { ic };
+                let src_offset = if has_memory_target { 2 } else { 1 };
 
                 let src_value = match (instr >> 1) & 0b11 {
                     // 8 bit register or 16 bit register
@@ -447,24 +1026,26 @@ impl Vm {
                         if has_memory_target {
                             used += 1;
                             // If the target is memory, we need to skip the second memory addres byte
-                            self.decode_registers(self.instructions.get(src_ic + 1))
+                            self.decode_registers(self.immediate_instr(src_offset + 1)?)
                                 .0
                                 .value
                         } else {
-                            self.decode_registers(self.instructions.get(src_ic)).1.value
+                            self.decode_registers(self.immediate_instr(src_offset)?)
+                                .1
+                                .value
                         }
                     }
                     // 8 bit immediate
                     0b10 => {
                         used += 1;
                         // If the source is not a register, immideate is stored in the next instruction
-                        self.immediate_instr(src_ic + 1).into()
+                        self.immediate_instr(src_offset + 1)?.into()
                     }
                     // 16 bit immediate
                     0b11 => {
                         used += 2;
                         // If the source is not a register, immideate is stored in the next instruction
-                        self.immediate_instr_16b(src_ic + 1).into()
+                        self.immediate_instr_16b(src_offset + 1)?.into()
                     }
                     _ => unreachable!(),
                 };
@@ -473,18 +1054,17 @@ impl Vm {
                     // Register target
                     0b0 => {
                         used += 1;
-                        let mut register = self.decode_register(self.instructions.get(ic));
+                        let mut register = self.decode_register(self.immediate_instr(1)?);
                         register.value = src_value;
                         self.register_save(register);
                     }
                     // Memory target target
                     0b1 => {
                         used += 2;
-                        // register addess is encoded the same way immediates are
-                        let addr = self.immediate_instr_16b(ic);
+                        let addr = self.memory_address(instr)?;
                         match src_value {
-                            Either::Left(value) => self.stack.save_value(addr, value),
-                            Either::Right(value) => self.stack.save_value_16(addr, value),
+                            Either::Left(value) => self.save_value(addr, value)?,
+                            Either::Right(value) => self.save_value_16(addr, value)?,
                         }
                     }
                     _ => unreachable!(),
@@ -492,103 +1072,183 @@ impl Vm {
             }
             // Load
             0b01 => {
-                // Address is decoded in the same way as immideates
-                let addr = self.immediate_instr_16b(ic);
+                let addr = self.memory_address(instr)?;
                 // The target register is always after the two address bytes
-                let mut target = self.decode_register(self.instructions.get(ic + 2));
+                let mut target = self.decode_register(self.immediate_instr(3)?);
 
                 // We can only load into registers
-                match instr & 0b1111 {
+                match instr & 0b1110 {
                     // 8 bit register
                     0b1000 => {
-                        let val = self.stack.load_value(addr);
+                        let val = self.load_value(addr)?;
                         target.value = val.into();
                     }
                     // 16 bit register
                     0b1010 => {
-                        let val = self.stack.load_value_16(addr);
+                        let val = self.load_value_16(addr)?;
                         target.value = val.into();
                     }
 
-                    _ => unreachable!("Invalid load instruction"),
+                    _ => return Err(self.invalid_opcode()),
                 }
 
                 self.register_save(target);
                 used += 3;
             }
-            0b11 => unimplemented!("Swap is not implemented"),
-            _ => unreachable!(),
+            // Swap
+            0b11 => {
+                let is_16b = instr & 0b10 != 0;
+                match instr & 0b1101 {
+                    // Register with register
+                    0b0000 => {
+                        let (mut target, mut source) =
+                            self.decode_registers(self.immediate_instr(1)?);
+                        std::mem::swap(&mut target.value, &mut source.value);
+                        self.register_save(target);
+                        self.register_save(source);
+                        used += 1;
+                    }
+                    // Register with memory, the register is after the two address bytes
+                    0b1000 | 0b1001 => {
+                        let addr = self.memory_address(instr)?;
+                        let mut target = self.decode_register(self.immediate_instr(3)?);
+                        if is_16b {
+                            let val = self.load_value_16(addr)?;
+                            self.save_value_16(addr, target.value.as_u16())?;
+                            target.value = val.into();
+                        } else {
+                            let val = self.load_value(addr)?;
+                            self.save_value(addr, target.value.as_u8())?;
+                            target.value = val.into();
+                        }
+                        self.register_save(target);
+                        used += 3;
+                    }
+                    _ => return Err(self.invalid_opcode()),
+                }
+            }
+            _ => return Err(self.invalid_opcode()),
         }
 
-        used
+        Ok(used)
+    }
+
+    /// Flag tested by the conditional branches, selected by the low bits of the opcode
+    fn branch_flag(&self, instr: u8) -> Result<u16, VmError> {
+        match instr & 0b111 {
+            0b000 => Ok(FG_EQUAL),
+            0b001 => Ok(FG_ZERO),
+            0b010 => Ok(FG_CARRY),
+            0b011 => Ok(FG_OVERFLOW),
+            0b100 => Ok(FG_SIGN),
+            0b101 => Ok(FG_ERROR),
+            _ => Err(self.invalid_opcode()),
+        }
     }
 
     /// First tuple value is true if a jump happens
-    fn decode_branch_instr(&mut self, instr: u8) -> (bool, u16) {
+    fn decode_branch_instr(&mut self, instr: u8) -> Result<(bool, u16), VmError> {
         let start_ic = self.registers.ic;
+        let op = (instr >> 3) & 0b111;
+
+        // Jump uses the low bits 0b000 and 0b001, call also uses 0b111 for syscall
+        let low = instr & 0b111;
+        if (op == 0b000 && low > 0b001) || (op == 0b101 && !matches!(low, 0b000 | 0b001 | 0b111)) {
+            return Err(self.invalid_opcode());
+        }
 
-        let address = if (instr >> 3) & 0b111 <= 0b100 {
-            self.immediate_instr_16b(self.registers.ic + 1)
+        // Jump and call read the target from a register if the low bits are 0b001
+        let is_indirect = matches!(op, 0b000 | 0b101) && low == 0b001;
+        let (address, used) = if is_indirect {
+            let target = self.decode_register(self.immediate_instr(1)?);
+            (target.value.as_u16(), 2)
+        } else if op <= 0b100 || (op == 0b101 && low != 0b111) {
+            (self.immediate_instr_16b(1)?, 3)
         } else {
-            // Since Only Branch/Jump will use the address, this value doesn't matter
-            0
+            // syscall and ret only use the opcode,
+            // since only Branch/Jump will use the address, this value doesn't matter
+            (0, 1)
         };
 
+        let mut jump: Option<u16> = None;
         match (instr >> 3) & 0b111 {
             // Relative jump
-            0b000 => self.registers.ic = address,
-            // Branch if equal
+            0b000 => jump = Some(address),
+            // Branch if flag set
             0b001 => {
-                if self.registers.fg & 0b1 == 1 {
-                    self.registers.ic = address
+                if self.registers.fg & self.branch_flag(instr)? != 0 {
+                    jump = Some(address)
                 }
             }
-            // Branch if not equal
+            // Branch if flag not set
             0b010 => {
-                if self.registers.fg & 0b1 == 0 {
-                    self.registers.ic = address
+                if self.registers.fg & self.branch_flag(instr)? == 0 {
+                    jump = Some(address)
                 }
             }
             // Branch if greater than
             0b011 => {
-                if (self.registers.fg >> 1) & 0b1 == 1 {
-                    self.registers.ic = address
+                if self.registers.fg & FG_GREATER != 0 {
+                    jump = Some(address)
                 }
             }
             // Branch if less than
             0b100 => {
-                if (self.registers.fg >> 2) & 0b1 == 1 {
-                    self.registers.ic = address
+                if self.registers.fg & FG_LESS != 0 {
+                    jump = Some(address)
                 }
             }
             // Call
             0b101 => {
                 if instr & 0b111 == 0b111 {
-                    vm_syscall(&mut self.registers, &mut self.stack);
+                    let result = self.syscalls.syscall(&mut self.registers, &mut self.stack);
+                    match result {
+                        Ok(SyscallOutcome::Continue) => {}
+                        Ok(SyscallOutcome::Exit(status)) => self.exit_status = Some(status),
+                        Err(err) => {
+                            let (ic, opcode) = self.fault();
+                            return Err(match err {
+                                SyscallError::NotImplemented(id) => {
+                                    VmError::UnimplementedSyscall { ic, opcode, id }
+                                }
+                            });
+                        }
+                    }
                 } else {
                     // Set the offset so we can return to after the call
-                    self.stack_push_16b(start_ic + 3);
-                    self.registers.ic = address
+                    self.stack_push_16b(start_ic.wrapping_add(used))?;
+                    jump = Some(address)
                 }
             }
             // Return from call
-            0b110 => self.registers.ic = self.stack_pop_16b(),
-            // Return from interrupt
-            0b111 => unimplemented!("Return from interrupt is not implemented"),
-            // Since we use and (&) we limit ourself to values 0-3
-            _ => unimplemented!("Only Add AluFamily is implemnted"),
+            0b110 => jump = Some(self.stack_pop_16b()?),
+            0b111 => match instr & 0b111 {
+                // Return from interrupt
+                0b000 => {
+                    self.registers.fg = self.stack_pop_16b()?;
+                    jump = Some(self.stack_pop_16b()?);
+                }
+                // Enable interrupts
+                0b001 => self.registers.fg |= FG_INTERRUPT,
+                // Disable interrupts
+                0b010 => self.registers.fg &= !FG_INTERRUPT,
+                _ => return Err(self.invalid_opcode()),
+            },
+            // Since we use and (&) we limit ourself to values 0-7
+            _ => unreachable!(),
         }
 
-        let has_jumped = start_ic != self.registers.ic;
-        // syscall and ret use 1 instruction, others use 3
-        if instr == 0b11101111 || (instr >> 3) & 0b110 == 0b110 {
-            (has_jumped, 1)
-        } else {
-            (has_jumped, 3)
+        // Jumping to the instruction itself is still a jump
+        match jump {
+            Some(address) => {
+                self.registers.ic = address;
+                Ok((true, used))
+            }
+            None => Ok((false, used)),
         }
     }
 
-    fn decode_stack_instr(&mut self, instr: u8) -> u16 {
+    fn decode_stack_instr(&mut self, instr: u8) -> Result<u16, VmError> {
         let mut used: u16;
         match (instr >> 4) & 0b11 {
             // Push
@@ -597,113 +1257,228 @@ impl Vm {
                 let value = match (instr >> 2) & 0b11 {
                     // 8 bit and 16 register has the same logic
                     0b00 | 0b01 => {
-                        let reg = self.instructions.get(self.registers.ic + 1);
+                        let reg = self.immediate_instr(1)?;
                         self.decode_register(reg).value
                     }
                     // 8 bit immideate
-                    0b10 => self.immediate_instr(self.registers.ic + 1).into(),
+                    0b10 => self.immediate_instr(1)?.into(),
                     // 16 bit immideate
                     0b11 => {
                         used = 3;
-                        self.immediate_instr_16b(self.registers.ic + 1).into()
+                        self.immediate_instr_16b(1)?.into()
                     }
                     _ => unreachable!(),
                 };
 
                 match value {
-                    Either::Left(val) => self.stack_push(val),
-                    Either::Right(val) => self.stack_push_16b(val),
+                    Either::Left(val) => self.stack_push(val)?,
+                    Either::Right(val) => self.stack_push_16b(val)?,
                 }
             }
             // Pop
             0b01 => {
                 // Can only pop into a register
                 used = 2;
-                let reg = self.instructions.get(self.registers.ic + 1);
+                let reg = self.immediate_instr(1)?;
                 let mut reg = self.decode_register(reg);
                 let popped = match (instr >> 2) & 0b1 == 0 {
-                    true => self.stack_pop().into(),
-                    false => self.stack_pop_16b().into(),
+                    true => self.stack_pop()?.into(),
+                    false => self.stack_pop_16b()?.into(),
                 };
                 reg.value = popped;
                 self.register_save(reg);
             }
             // Load variable
             0b10 => {
-                self.registers.vp = u16::MAX / 2;
                 // Set used to two since only 16 immideate uses 3 (self + 1/2)
                 used = 2;
-                match (instr >> 2) & 0b11 {
+                let offset = match (instr >> 2) & 0b11 {
                     // 8 bit and 16 register has the same logic
                     0b00 | 0b01 => {
-                        let reg = self.instructions.get(self.registers.ic + 1);
-                        self.registers.vp += self.decode_register(reg).value.as_u16();
+                        let reg = self.immediate_instr(1)?;
+                        self.decode_register(reg).value.as_u16()
                     }
                     // 8 bit immideate
-                    0b10 => {
-                        self.registers.vp += self.immediate_instr(self.registers.ic + 1) as u16;
-                    }
+                    0b10 => self.immediate_instr(1)? as u16,
                     // 16 bit immideate
                     0b11 => {
-                        self.registers.vp += self.immediate_instr_16b(self.registers.ic + 1);
                         used = 3;
+                        self.immediate_instr_16b(1)?
                     }
                     _ => unreachable!(),
-                }
+                };
+                let base = self.stack.layout().variable_start();
+                self.registers.vp = base.wrapping_add(offset);
             }
             // Unload variable
             0b11 => {
-                self.registers.vp = u16::MAX / 2;
+                self.registers.vp = self.stack.layout().variable_start();
                 used = 1;
             }
             // Since we use and (&) we limit ourself to values 0-3
-            _ => unimplemented!("decoding stack instr logic is broken"),
+            _ => unreachable!(),
         }
 
-        used
+        Ok(used)
     }
 
-    fn decode_next_instr(&mut self) {
-        let instr = self.instructions.get(self.registers.ic);
+    fn decode_next_instr(&mut self) -> Result<(), VmError> {
+        let ic = self.registers.ic;
+        let instr = self.instructions.get(ic);
+
+        let used = match (instr >> 6) & 0b11 {
+            0b00 => self.decode_alu_instr(instr)?,
+            0b01 => self.decode_load_store_instr(instr)?,
+            0b10 => self.decode_stack_instr(instr)?,
+            0b11 => match self.decode_branch_instr(instr)? {
+                (true, _) => return Ok(()),
+                (false, used) => used,
+            },
+            // Since we use and (&) we limit ourself to values 0-3
+            _ => unreachable!(),
+        };
 
-        match (instr >> 6) & 0b11 {
-            0b00 => {
-                let used = self.decode_alu_instr(instr);
-                self.registers.ic += used;
-            }
-            0b01 => {
-                let used = self.decode_load_store_instr(instr);
-                self.registers.ic += used;
-            }
-            0b10 => {
-                let used = self.decode_stack_instr(instr);
-                self.registers.ic += used;
+        // Fails if the instruction ends past the 16-bit address space
+        self.registers.ic = ic.checked_add(used).ok_or(VmError::InstructionOutOfRange {
+            ic,
+            opcode: Some(instr),
+        })?;
+        Ok(())
+    }
+
+    /// Map the console, timer and random devices to their default addresses
+    pub fn map_default_devices(&mut self, console: Console) {
+        let devices = [
+            self.bus.map(CONSOLE_BASE, 2, console),
+            self.bus.map(
+                TIMER_BASE,
+                5,
+                Timer::new(TIMER_LINE).expect("timer line exists"),
+            ),
+            self.bus.map(RANDOM_BASE, 1, Random::from_time()),
+        ];
+        for mapped in devices {
+            mapped.expect("default devices don't overlap");
+        }
+    }
+
+    /// Mark interrupt `line` as pending.
+    /// It's handled before the next instruction once interrupts are enabled.
+    /// Panics if `line` isn't below [INTERRUPT_LINES].
+    pub fn raise_interrupt(&mut self, line: u8) {
+        assert!(
+            line < INTERRUPT_LINES,
+            "interrupt line {line} is out of range"
+        );
+        self.pending_interrupts |= 1 << line;
+    }
+
+    /// Interrupt lines that were raised but not handled yet, bit `n` is line `n`
+    pub fn pending_interrupts(&self) -> u8 {
+        self.pending_interrupts
+    }
+
+    /// True if the next step enters an interrupt handler instead of executing `ic`
+    pub fn takes_interrupt(&self) -> bool {
+        self.registers.fg & FG_INTERRUPT != 0 && self.pending_interrupts != 0
+    }
+
+    /// Save `ic` and `fg` on the stack and jump to the handler of the lowest pending line.
+    /// Interrupts stay disabled until `reti` restores `fg`.
+    fn enter_interrupt(&mut self) -> Result<(), VmError> {
+        let line = self.pending_interrupts.trailing_zeros() as u16;
+        let handler = self.load_value_16(IVT_START + 2 * line)?;
+
+        self.stack_push_16b(self.registers.ic)?;
+        self.stack_push_16b(self.registers.fg)?;
+        self.pending_interrupts &= !(1 << line);
+        self.registers.fg &= !FG_INTERRUPT;
+        self.registers.ic = handler;
+        Ok(())
+    }
+
+    /// Status of the exit system call, None while the guest hasn't exited
+    pub fn exit_status(&self) -> Option<i32> {
+        self.exit_status
+    }
+
+    /// Execute exactly one instruction.
+    /// Returns [ExitReason::Finished] if there are no instructions left to execute
+    /// and [ExitReason::Exited] once the guest has exited.
+    pub fn step(&mut self) -> Result<Option<ExitReason>, VmError> {
+        if let Some(status) = self.exit_status {
+            return Ok(Some(ExitReason::Exited(status)));
+        }
+
+        let ic = self.registers.ic;
+
+        if ic as usize > self.instructions.size() {
+            return Err(VmError::InstructionOutOfRange { ic, opcode: None });
+        }
+
+        if ic as usize != self.instructions.size() {
+            if self.takes_interrupt() {
+                self.enter_interrupt()?;
+            } else {
+                self.decode_next_instr()?;
             }
-            0b11 => {
-                let ret = self.decode_branch_instr(instr);
-                if let (false, used) = ret {
-                    self.registers.ic += used;
+
+            let mut raised = Vec::new();
+            self.bus.tick(|line| raised.push(line));
+            for line in raised {
+                if line >= INTERRUPT_LINES {
+                    return Err(VmError::InvalidInterruptLine { ic, line });
                 }
+                self.raise_interrupt(line);
             }
-            // Since we use and (&) we limit ourself to values 0-3
-            _ => unreachable!(),
         }
+
+        if let Some(status) = self.exit_status {
+            return Ok(Some(ExitReason::Exited(status)));
+        }
+
+        // Stop after the last instruction
+        if self.registers.ic as usize == self.instructions.size() {
+            return Ok(Some(ExitReason::Finished));
+        }
+
+        Ok(None)
     }
 
-    pub fn run(&mut self) {
+    /// Run until the end of the instructions
+    pub fn run(&mut self) -> Result<ExitReason, VmError> {
         loop {
-            let ic = self.registers.ic;
+            if let Some(reason) = self.step()? {
+                return Ok(reason);
+            }
+        }
+    }
 
-            // Break after the last instruction
-            if ic as usize == self.instructions.size() {
-                break;
+    /// Run at most `count` instructions
+    pub fn run_for(&mut self, count: usize) -> Result<ExitReason, VmError> {
+        for _ in 0..count {
+            if let Some(reason) = self.step()? {
+                return Ok(reason);
             }
+        }
 
-            if ic as usize > self.instructions.size() {
-                panic!("Tried to access non-exsisitng instruction {}", ic);
+        Ok(ExitReason::InstructionLimit)
+    }
+
+    /// Run until `predicate` returns true.
+    /// `predicate` is checked before every instruction.
+    pub fn run_until<F>(&mut self, mut predicate: F) -> Result<ExitReason, VmError>
+    where
+        F: FnMut(&Vm) -> bool,
+    {
+        loop {
+            if predicate(self) {
+                return Ok(ExitReason::Predicate);
             }
 
-            self.decode_next_instr();
+            if let Some(reason) = self.step()? {
+                return Ok(reason);
+            }
         }
     }
 }
@@ -1,25 +1,66 @@
 use std::process::exit;
 
+use smol_vm::{debugger::Debugger, device::Console, ExitReason};
+
 fn main() {
     let args: Vec<String> = std::env::args().collect();
     if args.len() < 2 {
         println!("Give file as an argument");
+        println!("Usage: {} <file.obj> [--debug] [-- guest args...]", args[0]);
         exit(1);
     }
 
-    /* let file_contents = fs::read(&args[1]).unwrap(); */
-    let file = smol_file::SmolFile::load(&args[1]);
+    // Arguments after `--` belong to the guest, its first argument is the file
+    let (vm_args, guest_args) = match args.iter().position(|arg| arg == "--") {
+        Some(idx) => (&args[..idx], &args[idx + 1..]),
+        None => (&args[..], &[][..]),
+    };
+    let debug = vm_args.iter().skip(2).any(|arg| arg == "--debug");
+
+    let file = match smol_file::SmolFile::load(&args[1]) {
+        Ok(file) => file,
+        Err(err) => {
+            eprintln!("Error: {}: {err}", args[1]);
+            exit(1);
+        }
+    };
 
     let mut vm = smol_vm::Vm::default();
-    vm.registers.ic = file.main_start;
-    vm.instructions.instructions = file.instructions;
-    for storage in file.storage.items {
-        let mem = vm.stack.memory_mut();
-        if let Some(data) = storage.init_data {
-            let start = storage.offset as usize;
-            let end = start + storage.size as usize;
-            mem[start..end].copy_from_slice(&data);
+    #[cfg(unix)]
+    {
+        let guest_args = std::iter::once(&args[1]).chain(guest_args);
+        vm.syscalls = Box::new(smol_vm::syscall::Unix::new(guest_args));
+    }
+    // The debugger reads its commands from stdin, so the guest gets no input
+    let console = if debug {
+        Console::new(std::io::empty(), std::io::stdout())
+    } else {
+        Console::stdio()
+    };
+    vm.map_default_devices(console);
+    if let Err(err) = vm.load_file(file) {
+        eprintln!("Error: {}: {err}", args[1]);
+        exit(1);
+    }
+
+    if debug {
+        // Symbols are optional, without them the debugger only knows addresses
+        let sym_path = format!("{}.sym", args[1].trim_end_matches(".obj"));
+        let symbols = smol_file::load_symbols(&sym_path).unwrap_or_default();
+        let mut debugger = Debugger::new(vm, symbols);
+        if let Err(err) = debugger.repl(std::io::stdin().lock(), std::io::stdout()) {
+            eprintln!("Error: {err}");
+            exit(1);
+        }
+        return;
+    }
+
+    match vm.run() {
+        Ok(ExitReason::Exited(status)) => exit(status),
+        Ok(_) => {}
+        Err(err) => {
+            eprintln!("Error: {err}");
+            exit(1);
         }
     }
-    vm.run();
 }
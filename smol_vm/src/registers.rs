@@ -1,3 +1,32 @@
+/// Flags in the [Registers::fg] register
+pub const FG_EQUAL: u16 = 1 << 0;
+pub const FG_GREATER: u16 = 1 << 1;
+pub const FG_LESS: u16 = 1 << 2;
+pub const FG_ZERO: u16 = 1 << 3;
+pub const FG_CARRY: u16 = 1 << 4;
+pub const FG_OVERFLOW: u16 = 1 << 5;
+pub const FG_SIGN: u16 = 1 << 6;
+pub const FG_INTERRUPT: u16 = 1 << 7;
+pub const FG_ERROR: u16 = 1 << 8;
+
+/// Flags set by the equality instructions
+pub const FG_COMPARE: u16 = FG_EQUAL | FG_GREATER | FG_LESS;
+/// Flags set by the arithmetic instructions
+pub const FG_ARITHMETIC: u16 = FG_ZERO | FG_CARRY | FG_OVERFLOW | FG_SIGN;
+
+/// Flags with their names, in bit order
+pub const FG_NAMES: [(u16, &str); 9] = [
+    (FG_EQUAL, "eq"),
+    (FG_GREATER, "gt"),
+    (FG_LESS, "lt"),
+    (FG_ZERO, "zero"),
+    (FG_CARRY, "carry"),
+    (FG_OVERFLOW, "overflow"),
+    (FG_SIGN, "sign"),
+    (FG_INTERRUPT, "ie"),
+    (FG_ERROR, "err"),
+];
+
 #[derive(Debug, Default)]
 #[allow(dead_code)]
 pub struct Registers {
@@ -8,6 +37,12 @@ pub struct Registers {
     /// (1 >> 0) Is equal
     /// (1 >> 1) Is greater than
     /// (1 >> 2) Is less than
+    /// (1 >> 3) Arithmetic result is zero
+    /// (1 >> 4) Unsigned carry or borrow
+    /// (1 >> 5) Signed overflow
+    /// (1 >> 6) Arithmetic result is negative
+    /// (1 >> 7) Interrupts are enabled
+    /// (1 >> 8) Last system call failed
     pub fg: u16,
     /// (16,rw) - Call Register
     pub cr: u16,
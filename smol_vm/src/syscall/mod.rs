@@ -1,7 +1,195 @@
+//! System call interface of the guest, ABI version 2.
+//!
+//! | Register | Use                                             |
+//! |----------|-------------------------------------------------|
+//! | `r0`     | Call number                                     |
+//! | `r1`     | File descriptor or a small integer argument     |
+//! | `r2`     | Flags                                           |
+//! | `l0`     | Address of a buffer or a path, absolute         |
+//! | `l1`     | Length, count or mode                           |
+//! | `cr`     | Third 16-bit argument of the calls that need it |
+//!
+//! The 16-bit result is returned in `l0`. Calls with 32-bit values use `cr` for
+//! the high half, `cr:l0` in the table. The error flag [FG_ERROR] of `fg` is
+//! cleared on success. On failure it's set and `l0` has the errno of the host.
+//!
+//! | Number | Call        | Arguments                                 | Result                   |
+//! |--------|-------------|-------------------------------------------|--------------------------|
+//! | 0      | `read`      | `r1` fd, `l0` buffer, `l1` count          | Bytes read               |
+//! | 1      | `write`     | `r1` fd, `l0` buffer, `l1` count          | Bytes written            |
+//! | 2      | `open`      | `l0` path, `r2` `OPEN_*` flags, `l1` mode | File descriptor          |
+//! | 3      | `close`     | `r1` fd                                   | 0                        |
+//! | 4      | `stat`      | `l0` path, `cr` [stat buffer](STAT_SIZE)  | 0                        |
+//! | 5      | `fstat`     | `r1` fd, `l0` [stat buffer](STAT_SIZE)    | 0                        |
+//! | 8      | `lseek`     | `r1` fd, `cr:l0` offset, `r2` `SEEK_*`    | `cr:l0` new offset       |
+//! | 35     | `sleep`     | `l0` milliseconds                         | 0                        |
+//! | 39     | `getpid`    |                                           | `cr:l0` process id       |
+//! | 60     | `exit`      | `r1` status                               | Doesn't return           |
+//! | 100    | `getrandom` | `l0` buffer, `l1` count                   | Bytes written            |
+//! | 101    | `argc`      |                                           | Number of arguments      |
+//! | 102    | `argv`      | `r1` index, `l0` buffer, `l1` length      | Length of the argument   |
+//! | 103    | `getenv`    | `l0` name, `cr` buffer, `l1` length       | Length of the value      |
+//! | 201    | `time`      |                                           | `cr:l0` Unix time        |
+//! | 202    | `clock`     |                                           | `cr:l0` milliseconds     |
+//!
+//! Paths and names are NUL terminated. `argv` and `getenv` copy the string with
+//! a NUL terminator, they fail with `ERANGE` if it doesn't fit into the buffer.
+//! Offset of `lseek` is signed. `clock` counts from the start of the VM.
+//!
+//! Buffers have to be inside of the memory and outside of the guard region,
+//! otherwise the call fails with `EFAULT`.
+
 #[cfg(unix)]
 mod unix;
 
 #[cfg(unix)]
-use unix as imp;
+pub use unix::Unix;
+
+use std::{ffi::CStr, ops::Range};
+
+use crate::{registers::Registers, Stack, FG_ERROR};
+
+/// Version of the register conventions described in [self]
+pub const ABI_VERSION: u16 = 2;
+
+/// Open for reading only, the access mode is in the low two bits of the flags
+pub const OPEN_READ: u8 = 0b00;
+/// Open for writing only
+pub const OPEN_WRITE: u8 = 0b01;
+/// Open for reading and writing
+pub const OPEN_READ_WRITE: u8 = 0b10;
+/// Create the file if it doesn't exist, permissions are taken from the mode
+pub const OPEN_CREATE: u8 = 1 << 2;
+/// Truncate the file to zero length
+pub const OPEN_TRUNCATE: u8 = 1 << 3;
+/// Writes go to the end of the file
+pub const OPEN_APPEND: u8 = 1 << 4;
+/// Fail if the file exists, used with [OPEN_CREATE]
+pub const OPEN_EXCLUSIVE: u8 = 1 << 5;
+
+/// Errors returned by the system call interface
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyscallError {
+    /// System call with the id is not implemented
+    NotImplemented(u8),
+}
+
+/// What the [crate::Vm] does after the system call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyscallOutcome {
+    /// Continue from the next instruction
+    Continue,
+    /// Guest exited with the status, the VM halts
+    Exit(i32),
+}
+
+/// Handles the `syscall` instruction of the guest.
+/// The id of the call is in `r0`, the arguments and results in the other registers.
+pub trait SyscallHandler {
+    fn syscall(
+        &mut self,
+        registers: &mut Registers,
+        stack: &mut Stack,
+    ) -> Result<SyscallOutcome, SyscallError>;
+}
+
+/// Size of the buffer filled by `stat` and `fstat`, values are little endian.
+///
+/// * `0-3` - Size of the file in bytes, saturated
+/// * `4-5` - File type and permission bits of the mode
+/// * `6-9` - Last modification as Unix time, saturated
+pub const STAT_SIZE: u16 = 10;
+
+/// `lseek` from the start of the file
+pub const SEEK_SET: u8 = 0;
+/// `lseek` from the current offset
+pub const SEEK_CUR: u8 = 1;
+/// `lseek` from the end of the file
+pub const SEEK_END: u8 = 2;
+
+/// Return the 32-bit result of the call in `cr:l0`, the error is the errno
+pub fn set_result_32(registers: &mut Registers, result: Result<u32, u16>) {
+    match result {
+        Ok(value) => {
+            registers.cr = (value >> 16) as u16;
+            set_result(registers, Ok(value as u16));
+        }
+        Err(errno) => set_result(registers, Err(errno)),
+    }
+}
+
+/// Return the result of the call in `l0`, the error is the errno
+pub fn set_result(registers: &mut Registers, result: Result<u16, u16>) {
+    match result {
+        Ok(value) => {
+            registers.fg &= !FG_ERROR;
+            registers.l0 = value;
+        }
+        Err(errno) => {
+            registers.fg |= FG_ERROR;
+            registers.l0 = errno;
+        }
+    }
+}
+
+/// Range of a guest buffer, None if it isn't fully inside of the memory
+/// or touches the guard region
+fn guest_range(stack: &Stack, addr: usize, len: usize) -> Option<Range<usize>> {
+    let end = addr.checked_add(len)?;
+    let guard = stack.layout().guard();
+    if end > stack.memory().len() || (addr < guard.end && guard.start < end) {
+        return None;
+    }
+    Some(addr..end)
+}
+
+/// Guest buffer of `len` bytes from `addr`
+pub fn guest_buffer(stack: &Stack, addr: usize, len: usize) -> Option<&[u8]> {
+    let range = guest_range(stack, addr, len)?;
+    Some(&stack.memory()[range])
+}
+
+/// Writable guest buffer of `len` bytes from `addr`
+pub fn guest_buffer_mut(stack: &mut Stack, addr: usize, len: usize) -> Option<&mut [u8]> {
+    let range = guest_range(stack, addr, len)?;
+    Some(&mut stack.memory_mut()[range])
+}
+
+/// NUL terminated guest string from `addr`, the terminator must be inside of
+/// the same segment as the start
+pub fn guest_c_str(stack: &Stack, addr: usize) -> Option<&CStr> {
+    let layout = stack.layout();
+    let after_guard = layout.guard().end..stack.memory().len();
+    let segment = [layout.stack(), after_guard]
+        .into_iter()
+        .find(|segment| segment.contains(&addr))?;
+    CStr::from_bytes_until_nul(&stack.memory()[addr..segment.end]).ok()
+}
+
+impl std::fmt::Debug for dyn SyscallHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SyscallHandler")
+    }
+}
+
+/// Sandbox that doesn't implement any of the calls
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DenyAll;
+
+impl SyscallHandler for DenyAll {
+    fn syscall(
+        &mut self,
+        registers: &mut Registers,
+        _: &mut Stack,
+    ) -> Result<SyscallOutcome, SyscallError> {
+        Err(SyscallError::NotImplemented(registers.r0))
+    }
+}
 
-pub use imp::vm_syscall;
+/// Handler of the host, [DenyAll] if the host has no system call implementation
+pub fn host() -> Box<dyn SyscallHandler> {
+    #[cfg(unix)]
+    return Box::new(Unix::default());
+    #[cfg(not(unix))]
+    return Box::new(DenyAll);
+}
@@ -1,70 +1,277 @@
+use std::{
+    ffi::OsStr,
+    io::Read,
+    os::unix::ffi::OsStrExt,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use super::{
+    guest_buffer, guest_buffer_mut, guest_c_str, set_result, set_result_32, SyscallError,
+    SyscallHandler, SyscallOutcome, OPEN_APPEND, OPEN_CREATE, OPEN_EXCLUSIVE, OPEN_READ,
+    OPEN_READ_WRITE, OPEN_TRUNCATE, OPEN_WRITE, SEEK_CUR, SEEK_END, SEEK_SET, STAT_SIZE,
+};
 use crate::{registers::Registers, Stack};
 
-/// Sycall interface, "return" value will be in r0
-pub fn vm_syscall(register: &mut Registers, stack: &mut Stack) {
-    match register.r0 {
-        0 => vm_syscall_read(register, stack),
-        1 => vm_syscall_write(register, stack),
-        2 => vm_syscall_open(register, stack),
-        3 => vm_syscall_close(register),
-        60 => vm_syscall_exit(register),
-        id => panic!("System call with id: '{id}' is not implemented for linux"),
+/// Passes the calls to the host with libc
+#[derive(Debug, Clone)]
+pub struct Unix {
+    /// Arguments of the guest program, the first one is the name of the program
+    args: Vec<Vec<u8>>,
+    /// Start of the `clock` call
+    start: Instant,
+}
+
+impl Unix {
+    pub fn new<I, S>(args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        Self {
+            args: args
+                .into_iter()
+                .map(|arg| arg.as_ref().as_bytes().to_vec())
+                .collect(),
+            start: Instant::now(),
+        }
+    }
+
+    fn vm_syscall_argv(&self, register: &mut Registers, stack: &mut Stack) -> Result<u16, u16> {
+        let arg = self
+            .args
+            .get(register.r1 as usize)
+            .ok_or(libc::EINVAL as u16)?;
+        copy_string(register, stack, register.l0, arg)
     }
 }
 
-fn vm_syscall_read(register: &mut Registers, stack: &mut Stack) {
-    let sp = register.vp + register.r2 as u16;
+impl Default for Unix {
+    /// Guest without arguments
+    fn default() -> Self {
+        Self::new::<_, &OsStr>([])
+    }
+}
 
-    let fd = register.r1 as libc::c_int;
-    let buf = stack.from_sp_mut(sp).as_mut_ptr() as *mut libc::c_void;
-    let count = register.r3 as libc::size_t;
+impl SyscallHandler for Unix {
+    /// Sycall interface, see [super] for the register conventions
+    fn syscall(
+        &mut self,
+        register: &mut Registers,
+        stack: &mut Stack,
+    ) -> Result<SyscallOutcome, SyscallError> {
+        let result = match register.r0 {
+            0 => vm_syscall_read(register, stack),
+            1 => vm_syscall_write(register, stack),
+            2 => vm_syscall_open(register, stack),
+            3 => vm_syscall_close(register),
+            4 => vm_syscall_stat(register, stack),
+            5 => vm_syscall_fstat(register, stack),
+            8 => {
+                let offset = vm_syscall_lseek(register);
+                set_result_32(register, offset);
+                return Ok(SyscallOutcome::Continue);
+            }
+            35 => vm_syscall_sleep(register),
+            39 => {
+                set_result_32(register, Ok(std::process::id()));
+                return Ok(SyscallOutcome::Continue);
+            }
+            // Only the VM stops and not the host process
+            60 => return Ok(SyscallOutcome::Exit(register.r1 as i32)),
+            100 => vm_syscall_getrandom(register, stack),
+            101 => Ok(self.args.len() as u16),
+            102 => self.vm_syscall_argv(register, stack),
+            103 => vm_syscall_getenv(register, stack),
+            201 => {
+                let time = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|time| time.as_secs())
+                    .unwrap_or_default();
+                set_result_32(register, Ok(time as u32));
+                return Ok(SyscallOutcome::Continue);
+            }
+            202 => {
+                let millis = self.start.elapsed().as_millis();
+                set_result_32(register, Ok(millis as u32));
+                return Ok(SyscallOutcome::Continue);
+            }
+            id => return Err(SyscallError::NotImplemented(id)),
+        };
 
-    // SAFETY: always safe to call.
-    let out = unsafe { libc::read(fd, buf, count) };
+        set_result(register, result);
+        Ok(SyscallOutcome::Continue)
+    }
+}
+
+/// Result of the call, failures return the errno of the host
+fn result(out: isize) -> Result<u16, u16> {
+    if out >= 0 {
+        return Ok(out as u16);
+    }
 
-    register.r0 = out as u8;
+    let errno = std::io::Error::last_os_error()
+        .raw_os_error()
+        .unwrap_or(libc::EIO);
+    Err(errno as u16)
 }
 
-fn vm_syscall_write(register: &mut Registers, stack: &mut Stack) {
-    let sp = register.vp + register.r2 as u16;
+fn vm_syscall_read(register: &mut Registers, stack: &mut Stack) -> Result<u16, u16> {
+    let fd = register.r1 as libc::c_int;
+    let count = register.l1 as usize;
+    let buf = guest_buffer_mut(stack, register.l0 as usize, count).ok_or(libc::EFAULT as u16)?;
+
+    // SAFETY: buf is valid for writes of `count` bytes.
+    let out = unsafe { libc::read(fd, buf.as_mut_ptr() as *mut libc::c_void, count) };
+
+    result(out)
+}
 
+fn vm_syscall_write(register: &mut Registers, stack: &mut Stack) -> Result<u16, u16> {
     let fd = register.r1 as libc::c_int;
-    let buf = stack.from_sp_mut(sp).as_mut_ptr() as *const libc::c_void;
-    let count = register.r3 as libc::size_t;
+    let count = register.l1 as usize;
+    let buf = guest_buffer(stack, register.l0 as usize, count).ok_or(libc::EFAULT as u16)?;
 
-    // SAFETY: always safe to call.
-    let out = unsafe { libc::write(fd, buf, count) };
+    // SAFETY: buf is valid for reads of `count` bytes.
+    let out = unsafe { libc::write(fd, buf.as_ptr() as *const libc::c_void, count) };
 
-    register.r0 = out as u8;
+    result(out)
 }
 
-fn vm_syscall_open(register: &mut Registers, stack: &mut Stack) {
-    let sp = register.vp + register.r1 as u16;
+/// Host flags of the portable guest open flags
+fn open_flags(flags: u8) -> Result<libc::c_int, u16> {
+    let known = 0b11 | OPEN_CREATE | OPEN_TRUNCATE | OPEN_APPEND | OPEN_EXCLUSIVE;
+    if flags & !known != 0 {
+        return Err(libc::EINVAL as u16);
+    }
+
+    let mut host = match flags & 0b11 {
+        OPEN_READ => libc::O_RDONLY,
+        OPEN_WRITE => libc::O_WRONLY,
+        OPEN_READ_WRITE => libc::O_RDWR,
+        _ => return Err(libc::EINVAL as u16),
+    };
+    for (flag, host_flag) in [
+        (OPEN_CREATE, libc::O_CREAT),
+        (OPEN_TRUNCATE, libc::O_TRUNC),
+        (OPEN_APPEND, libc::O_APPEND),
+        (OPEN_EXCLUSIVE, libc::O_EXCL),
+    ] {
+        if flags & flag != 0 {
+            host |= host_flag;
+        }
+    }
+    Ok(host)
+}
 
-    let buf = stack.from_sp_mut(sp).as_mut_ptr() as *const libc::c_char;
-    // TODO: Figure out how to handle flags and mode with eight bits
-    let _flags = register.r2 as libc::size_t;
-    let _mode = register.r3 as libc::size_t;
+fn vm_syscall_open(register: &mut Registers, stack: &mut Stack) -> Result<u16, u16> {
+    let flags = open_flags(register.r2)?;
+    let mode = register.l1 as libc::c_uint;
+    let path = guest_c_str(stack, register.l0 as usize).ok_or(libc::EFAULT as u16)?;
 
-    // SAFETY: always safe to call.
-    let out = unsafe { libc::open(buf, 0, 0) };
+    // SAFETY: path is NUL terminated.
+    let out = unsafe { libc::open(path.as_ptr(), flags, mode) };
 
-    register.r0 = out as u8;
+    result(out as isize)
 }
 
-fn vm_syscall_close(register: &mut Registers) {
+fn vm_syscall_close(register: &mut Registers) -> Result<u16, u16> {
     let fd = register.r1 as libc::c_int;
     // SAFETY: always safe to call.
     let out = unsafe { libc::close(fd) };
 
-    register.r0 = out as u8;
+    result(out as isize)
+}
+
+/// Copy the string with a NUL terminator into the buffer of `l1` bytes at `addr`
+fn copy_string(
+    register: &Registers,
+    stack: &mut Stack,
+    addr: u16,
+    string: &[u8],
+) -> Result<u16, u16> {
+    let len = string.len();
+    if len >= register.l1 as usize {
+        return Err(libc::ERANGE as u16);
+    }
+
+    let buf = guest_buffer_mut(stack, addr as usize, len + 1).ok_or(libc::EFAULT as u16)?;
+    buf[..len].copy_from_slice(string);
+    buf[len] = 0;
+    Ok(len as u16)
 }
 
-fn vm_syscall_exit(register: &mut Registers) {
-    let status = register.r1 as libc::c_int;
+/// Write the guest stat structure, see [STAT_SIZE]
+fn write_stat(stack: &mut Stack, addr: u16, stat: &libc::stat) -> Result<u16, u16> {
+    let buf =
+        guest_buffer_mut(stack, addr as usize, STAT_SIZE as usize).ok_or(libc::EFAULT as u16)?;
+
+    let size = u32::try_from(stat.st_size).unwrap_or(u32::MAX);
+    let mtime = u32::try_from(stat.st_mtime).unwrap_or(u32::MAX);
+    buf[0..4].copy_from_slice(&size.to_le_bytes());
+    buf[4..6].copy_from_slice(&(stat.st_mode as u16).to_le_bytes());
+    buf[6..10].copy_from_slice(&mtime.to_le_bytes());
+    Ok(0)
+}
 
+fn vm_syscall_stat(register: &mut Registers, stack: &mut Stack) -> Result<u16, u16> {
+    let path = guest_c_str(stack, register.l0 as usize).ok_or(libc::EFAULT as u16)?;
+
+    // SAFETY: stat is plain data that libc fills.
+    let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+    // SAFETY: path is NUL terminated.
+    let out = unsafe { libc::stat(path.as_ptr(), &mut stat) };
+    result(out as isize)?;
+
+    write_stat(stack, register.cr, &stat)
+}
+
+fn vm_syscall_fstat(register: &mut Registers, stack: &mut Stack) -> Result<u16, u16> {
+    let fd = register.r1 as libc::c_int;
+
+    // SAFETY: stat is plain data that libc fills.
+    let mut stat: libc::stat = unsafe { std::mem::zeroed() };
     // SAFETY: always safe to call.
-    unsafe {
-        libc::exit(status);
-    }
+    let out = unsafe { libc::fstat(fd, &mut stat) };
+    result(out as isize)?;
+
+    write_stat(stack, register.l0, &stat)
+}
+
+fn vm_syscall_lseek(register: &mut Registers) -> Result<u32, u16> {
+    let fd = register.r1 as libc::c_int;
+    let offset = (((register.cr as u32) << 16) | register.l0 as u32) as i32;
+    let whence = match register.r2 {
+        SEEK_SET => libc::SEEK_SET,
+        SEEK_CUR => libc::SEEK_CUR,
+        SEEK_END => libc::SEEK_END,
+        _ => return Err(libc::EINVAL as u16),
+    };
+
+    // SAFETY: always safe to call.
+    let out = unsafe { libc::lseek(fd, offset as libc::off_t, whence) };
+    result(out as isize)?;
+
+    u32::try_from(out).map_err(|_| libc::EOVERFLOW as u16)
+}
+
+fn vm_syscall_sleep(register: &mut Registers) -> Result<u16, u16> {
+    std::thread::sleep(Duration::from_millis(register.l0 as u64));
+    Ok(0)
+}
+
+fn vm_syscall_getrandom(register: &mut Registers, stack: &mut Stack) -> Result<u16, u16> {
+    let count = register.l1 as usize;
+    let buf = guest_buffer_mut(stack, register.l0 as usize, count).ok_or(libc::EFAULT as u16)?;
+
+    let io_errno = |err: std::io::Error| err.raw_os_error().unwrap_or(libc::EIO) as u16;
+    let mut random = std::fs::File::open("/dev/urandom").map_err(io_errno)?;
+    random.read_exact(buf).map_err(io_errno)?;
+    Ok(count as u16)
+}
+
+fn vm_syscall_getenv(register: &mut Registers, stack: &mut Stack) -> Result<u16, u16> {
+    let name = guest_c_str(stack, register.l0 as usize).ok_or(libc::EFAULT as u16)?;
+    let name = OsStr::from_bytes(name.to_bytes()).to_os_string();
+    let value = std::env::var_os(name).ok_or(libc::ENOENT as u16)?;
+    copy_string(register, stack, register.cr, value.as_bytes())
 }
@@ -11,7 +11,7 @@ pub fn it_adds_r0_r1() {
         // Registers r0 and r1
         0b0001_0000,
     ];
-    vm.run();
+    vm.run().unwrap();
 
     assert_eq!(vm.registers.r0, 3);
 }
@@ -27,7 +27,7 @@ pub fn it_adds_r2_r3() {
         // Registers r2 and r3
         0b0011_0010,
     ];
-    vm.run();
+    vm.run().unwrap();
 
     assert_eq!(vm.registers.r2, 8);
 }
@@ -43,7 +43,7 @@ pub fn it_adds_r7_r4() {
         // Registers r7 and r4
         0b0100_0111,
     ];
-    vm.run();
+    vm.run().unwrap();
 
     assert_eq!(vm.registers.r7, 111);
 }
@@ -60,7 +60,7 @@ pub fn it_iadds_r7_123() {
         // Immediate 11
         11,
     ];
-    vm.run();
+    vm.run().unwrap();
 
     assert_eq!(vm.registers.r7, 111);
 }
@@ -76,7 +76,7 @@ pub fn it_substracts_r0_r1() {
         // Registers r0 and r1
         0b0001_0000,
     ];
-    vm.run();
+    vm.run().unwrap();
 
     assert_eq!(vm.registers.r0, 3);
 }
@@ -92,7 +92,7 @@ pub fn it_substracts_r2_r3() {
         // Registers r2 and r3
         0b0011_0010,
     ];
-    vm.run();
+    vm.run().unwrap();
 
     assert_eq!(vm.registers.r2, 8);
 }
@@ -108,7 +108,7 @@ pub fn it_substracts_r7_r4() {
         // Registers r7 and r4
         0b0100_0111,
     ];
-    vm.run();
+    vm.run().unwrap();
 
     assert_eq!(vm.registers.r7, 111);
 }
@@ -124,7 +124,7 @@ pub fn it_binary_ands_r0_r1() {
         // Registers r0 and r1
         0b0001_0000,
     ];
-    vm.run();
+    vm.run().unwrap();
 
     assert_eq!(vm.registers.r0, 4);
 }
@@ -140,7 +140,7 @@ pub fn it_binary_ands_r2_r3() {
         // Registers r2 and r3
         0b0011_0010,
     ];
-    vm.run();
+    vm.run().unwrap();
 
     assert_eq!(vm.registers.r2, 8);
 }
@@ -156,7 +156,7 @@ pub fn it_binary_ands_r7_r4() {
         // Registers r7 and r4
         0b0100_0111,
     ];
-    vm.run();
+    vm.run().unwrap();
 
     assert_eq!(vm.registers.r7, 111);
 }
@@ -172,7 +172,7 @@ pub fn it_binary_ors_r0_r1() {
         // Registers r0 and r1
         0b0001_0000,
     ];
-    vm.run();
+    vm.run().unwrap();
 
     assert_eq!(vm.registers.r0, 5);
 }
@@ -188,7 +188,7 @@ pub fn it_binary_ors_r2_r3() {
         // Registers r2 and r3
         0b0011_0010,
     ];
-    vm.run();
+    vm.run().unwrap();
 
     assert_eq!(vm.registers.r2, 14);
 }
@@ -204,7 +204,7 @@ pub fn it_binary_ors_r7_r4() {
         // Registers r7 and r4
         0b0100_0111,
     ];
-    vm.run();
+    vm.run().unwrap();
 
     assert_eq!(vm.registers.r7, 111);
 }
@@ -220,7 +220,7 @@ pub fn it_binary_xors_r0_r1() {
         // Registers r0 and r1
         0b0001_0000,
     ];
-    vm.run();
+    vm.run().unwrap();
 
     assert_eq!(vm.registers.r0, 1);
 }
@@ -236,7 +236,7 @@ pub fn it_binary_xors_r2_r3() {
         // Registers r2 and r3
         0b0011_0010,
     ];
-    vm.run();
+    vm.run().unwrap();
 
     assert_eq!(vm.registers.r2, 6);
 }
@@ -252,7 +252,7 @@ pub fn it_binary_xors_r7_r4() {
         // Registers r7 and r4
         0b0100_0111,
     ];
-    vm.run();
+    vm.run().unwrap();
 
     assert_eq!(vm.registers.r7, 1);
 }
@@ -268,7 +268,7 @@ pub fn it_binary_nots_r0() {
         // Register r0
         0b000_0000,
     ];
-    vm.run();
+    vm.run().unwrap();
 
     assert_eq!(vm.registers.r0, 0b0000010);
 }
@@ -283,7 +283,7 @@ pub fn it_binary_nots_r2() {
         // Register r2
         0b0000_0010,
     ];
-    vm.run();
+    vm.run().unwrap();
 
     assert_eq!(vm.registers.r2, 132);
 }
@@ -298,7 +298,7 @@ pub fn it_binary_nots_r7() {
         // Register r 7
         0b0000_0111,
     ];
-    vm.run();
+    vm.run().unwrap();
 
     assert_eq!(vm.registers.r7, 0b1001_0000);
 }
@@ -313,7 +313,7 @@ pub fn it_increments_r0() {
         // Register r0
         0b000_0000,
     ];
-    vm.run();
+    vm.run().unwrap();
 
     assert_eq!(vm.registers.r0, 0b111110);
 }
@@ -328,7 +328,7 @@ pub fn it_increments_r2() {
         // Register r2
         0b0000_0010,
     ];
-    vm.run();
+    vm.run().unwrap();
 
     assert_eq!(vm.registers.r2, 124);
 }
@@ -343,7 +343,7 @@ pub fn it_decrements_r7() {
         // Register r 7
         0b0000_0111,
     ];
-    vm.run();
+    vm.run().unwrap();
 
     assert_eq!(vm.registers.r7, 0b01101110);
 }
@@ -359,7 +359,7 @@ pub fn it_sets_eq_flag_register() {
         // Register r7 and r6
         0b0110_0111,
     ];
-    vm.run();
+    vm.run().unwrap();
 
     assert_eq!(vm.registers.fg, 0b1);
 }
@@ -375,7 +375,7 @@ pub fn it_sets_eq_flag_immidiate() {
         0b0000_0111,
         50,
     ];
-    vm.run();
+    vm.run().unwrap();
 
     assert_eq!(vm.registers.fg, 0b1);
 }
@@ -391,7 +391,7 @@ pub fn it_sets_gt_flag_register() {
         // Register r7 and r6
         0b0110_0111,
     ];
-    vm.run();
+    vm.run().unwrap();
 
     assert_eq!(vm.registers.fg, 0b10);
 }
@@ -407,7 +407,7 @@ pub fn it_sets_gt_flag_immidiate() {
         0b0000_0111,
         49,
     ];
-    vm.run();
+    vm.run().unwrap();
 
     assert_eq!(vm.registers.fg, 0b10);
 }
@@ -423,7 +423,7 @@ pub fn it_sets_lt_flag_register() {
         // Register r7 and r6
         0b0110_0111,
     ];
-    vm.run();
+    vm.run().unwrap();
 
     assert_eq!(vm.registers.fg, 0b100);
 }
@@ -439,7 +439,351 @@ pub fn it_sets_lt_flag_immidiate() {
         0b0000_0111,
         60,
     ];
-    vm.run();
+    vm.run().unwrap();
 
     assert_eq!(vm.registers.fg, 0b100);
 }
+
+#[test]
+pub fn it_adds_l0_l1() {
+    let mut vm = Vm::default();
+    vm.registers.l0 = 1000;
+    vm.registers.l1 = 2000;
+    vm.instructions.instructions = vec![
+        // ALU Add from 16-bit Register
+        0b00_000_0_1_0,
+        // Registers l0 and l1
+        0b1010_1001,
+    ];
+    vm.run().unwrap();
+
+    assert_eq!(vm.registers.l0, 3000);
+    assert_eq!(vm.registers.ic, 2);
+}
+
+#[test]
+pub fn it_iadds_16b_l1() {
+    let mut vm = Vm::default();
+    vm.registers.l1 = 300;
+    vm.instructions.instructions = vec![
+        // ALU Add from 16-bit Immediate
+        0b00_000_1_1_0,
+        // Register l1
+        0b0000_1010,
+        // Immediate 1000 in 16 bit little endian
+        0b11101000,
+        0b00000011,
+    ];
+    vm.run().unwrap();
+
+    assert_eq!(vm.registers.l1, 1300);
+    assert_eq!(vm.registers.ic, 4);
+}
+
+#[test]
+pub fn it_substracts_16b_immediate_from_sp() {
+    let mut vm = Vm::default();
+    vm.registers.sp = 1000;
+    vm.instructions.instructions = vec![
+        // ALU Subtract from 16-bit Immediate
+        0b00_001_1_1_0,
+        // Register sp
+        0b0000_1110,
+        // Immediate 258 in 16 bit little endian
+        0b00000010,
+        0b00000001,
+    ];
+    vm.run().unwrap();
+
+    assert_eq!(vm.registers.sp, 742);
+}
+
+#[test]
+pub fn it_binary_nots_l0() {
+    let mut vm = Vm::default();
+    vm.registers.l0 = 0b1111_0000_1111_0000;
+    vm.instructions.instructions = vec![
+        // ALU Binary not from 16-bit Register
+        0b00_101_0_1_0,
+        // Register l0
+        0b0000_1001,
+    ];
+    vm.run().unwrap();
+
+    assert_eq!(vm.registers.l0, 0b0000_1111_0000_1111);
+}
+
+#[test]
+pub fn it_increments_16b_vp() {
+    let mut vm = Vm::default();
+    vm.registers.vp = 255;
+    vm.instructions.instructions = vec![
+        // ALU Incerement from 16-bit Register
+        0b00_111_0_1_0,
+        // Register vp
+        0b0000_1000,
+    ];
+    vm.run().unwrap();
+
+    assert_eq!(vm.registers.vp, 256);
+}
+
+#[test]
+pub fn it_decrements_16b_cr() {
+    let mut vm = Vm::default();
+    vm.registers.cr = 256;
+    vm.instructions.instructions = vec![
+        // ALU Decrement from 16-bit Register
+        0b00_111_1_1_0,
+        // Register cr
+        0b0000_1101,
+    ];
+    vm.run().unwrap();
+
+    assert_eq!(vm.registers.cr, 255);
+}
+
+#[test]
+pub fn it_uses_8b_width_without_16b_flag() {
+    let mut vm = Vm::default();
+    vm.registers.l0 = 0x1234;
+    vm.registers.r1 = 1;
+    vm.instructions.instructions = vec![
+        // ALU Add from Register
+        0b00_000_0_0_0,
+        // Registers l0 and r1
+        0b0001_1001,
+    ];
+    vm.run().unwrap();
+
+    assert_eq!(vm.registers.l0, 0x35);
+}
+
+#[test]
+pub fn it_sets_eq_flag_16b_immidiate() {
+    let mut vm = Vm::default();
+    vm.registers.l0 = 1000;
+    vm.registers.r0 = 1;
+    vm.instructions.instructions = vec![
+        // ALU EQIL
+        0b00_110_1_1_0,
+        // Register l0
+        0b0000_1001,
+        // Immediate 1000 in 16 bit little endian
+        0b11101000,
+        0b00000011,
+        // ALU Add from Register
+        0b00_000_0_0_0,
+        // Registers r0 and r0
+        0b0000_0000,
+    ];
+    vm.run().unwrap();
+
+    assert_eq!(vm.registers.fg, 0b1);
+    assert_eq!(vm.registers.l0, 1000);
+    assert_eq!(vm.registers.r0, 2);
+}
+
+#[test]
+pub fn it_sets_gt_flag_16b_register() {
+    let mut vm = Vm::default();
+    vm.registers.l0 = 1000;
+    vm.registers.l1 = 999;
+    vm.instructions.instructions = vec![
+        // ALU EQRL
+        0b00_110_0_1_0,
+        // Registers l0 and l1
+        0b1010_1001,
+    ];
+    vm.run().unwrap();
+
+    assert_eq!(vm.registers.fg, 0b10);
+}
+
+#[test]
+pub fn it_wraps_and_sets_carry_and_zero_flags() {
+    let mut vm = Vm::default();
+    vm.registers.r0 = 255;
+    vm.registers.r1 = 1;
+    vm.instructions.instructions = vec![
+        // ALU Add from Register
+        0b00_000_0_0_0,
+        // Registers r0 and r1
+        0b0001_0000,
+    ];
+    vm.run().unwrap();
+
+    assert_eq!(vm.registers.r0, 0);
+    // Zero and carry
+    assert_eq!(vm.registers.fg, 0b0001_1000);
+}
+
+#[test]
+pub fn it_sets_overflow_and_sign_flags() {
+    let mut vm = Vm::default();
+    vm.registers.r0 = 127;
+    vm.instructions.instructions = vec![
+        // ALU Incerement from Register
+        0b00_111_0_0_0,
+        // Register r0
+        0b0000_0000,
+    ];
+    vm.run().unwrap();
+
+    assert_eq!(vm.registers.r0, 128);
+    // Overflow and sign
+    assert_eq!(vm.registers.fg, 0b0110_0000);
+}
+
+#[test]
+pub fn it_sets_borrow_flag_16b() {
+    let mut vm = Vm::default();
+    vm.registers.l0 = 1;
+    vm.registers.l1 = 2;
+    vm.instructions.instructions = vec![
+        // ALU Subtract from 16-bit Register
+        0b00_001_0_1_0,
+        // Registers l0 and l1
+        0b1010_1001,
+    ];
+    vm.run().unwrap();
+
+    assert_eq!(vm.registers.l0, 0xffff);
+    // Carry and sign
+    assert_eq!(vm.registers.fg, 0b0101_0000);
+}
+
+#[test]
+pub fn it_keeps_arithmetic_flags_on_equality() {
+    let mut vm = Vm::default();
+    vm.registers.r0 = 1;
+    vm.instructions.instructions = vec![
+        // ALU Decrement from Register
+        0b00_111_1_0_0,
+        // Register r0
+        0b0000_0000,
+        // ALU EQI
+        0b00_110_1_0_0,
+        // Register r0
+        0b0000_0000,
+        0,
+    ];
+    vm.run().unwrap();
+
+    // Zero and equal
+    assert_eq!(vm.registers.fg, 0b0000_1001);
+}
+
+#[test]
+pub fn it_multiplies_r0_r1() {
+    let mut vm = Vm::default();
+    vm.registers.r0 = 12;
+    vm.registers.r1 = 11;
+    vm.instructions.instructions = vec![
+        // ALU Multiply from Register
+        0b00_000_0_0_1,
+        // Registers r0 and r1
+        0b0001_0000,
+    ];
+    vm.run().unwrap();
+
+    assert_eq!(vm.registers.r0, 132);
+}
+
+#[test]
+pub fn it_multiplies_16b_immediate_wrapping() {
+    let mut vm = Vm::default();
+    vm.registers.l0 = 0x4001;
+    vm.instructions.instructions = vec![
+        // ALU Multiply from 16-bit Immediate
+        0b00_000_1_1_1,
+        // Register l0
+        0b0000_1001,
+        // Immediate 4 in 16 bit little endian
+        4,
+        0,
+    ];
+    vm.run().unwrap();
+
+    assert_eq!(vm.registers.l0, 4);
+    assert_eq!(vm.registers.ic, 4);
+}
+
+#[test]
+pub fn it_divides_and_modulos_16b() {
+    let mut vm = Vm::default();
+    vm.registers.l0 = 1234;
+    vm.registers.l1 = 1234;
+    vm.instructions.instructions = vec![
+        // ALU Divide from 16-bit Immediate
+        0b00_001_1_1_1,
+        // Register l0
+        0b0000_1001,
+        // Immediate 10 in 16 bit little endian
+        10,
+        0,
+        // ALU Modulo from 16-bit Immediate
+        0b00_010_1_1_1,
+        // Register l1
+        0b0000_1010,
+        // Immediate 10 in 16 bit little endian
+        10,
+        0,
+    ];
+    vm.run().unwrap();
+
+    assert_eq!(vm.registers.l0, 123);
+    assert_eq!(vm.registers.l1, 4);
+}
+
+#[test]
+pub fn it_shifts_r0() {
+    let mut vm = Vm::default();
+    vm.registers.r0 = 0b1000_0110;
+    vm.registers.r1 = 0b1000_0110;
+    vm.registers.r2 = 0b1000_0110;
+    vm.instructions.instructions = vec![
+        // ALU Shift left from Immediate
+        0b00_011_1_0_1,
+        // Register r0
+        0b0000_0000,
+        1,
+        // ALU Logical shift right from Immediate
+        0b00_100_1_0_1,
+        // Register r1
+        0b0000_0001,
+        1,
+        // ALU Arithmetic shift right from Immediate
+        0b00_101_1_0_1,
+        // Register r2
+        0b0000_0010,
+        1,
+    ];
+    vm.run().unwrap();
+
+    assert_eq!(vm.registers.r0, 0b0000_1100);
+    assert_eq!(vm.registers.r1, 0b0100_0011);
+    assert_eq!(vm.registers.r2, 0b1100_0011);
+}
+
+#[test]
+pub fn it_shifts_past_the_width() {
+    let mut vm = Vm::default();
+    vm.registers.l0 = 0x8001;
+    vm.registers.l1 = 0x8001;
+    vm.registers.r0 = 16;
+    vm.instructions.instructions = vec![
+        // ALU Shift left from 16-bit Register
+        0b00_011_0_1_1,
+        // Registers l0 and r0
+        0b0000_1001,
+        // ALU Arithmetic shift right from 16-bit Register
+        0b00_101_0_1_1,
+        // Registers l1 and r0
+        0b0000_1010,
+    ];
+    vm.run().unwrap();
+
+    assert_eq!(vm.registers.l0, 0);
+    assert_eq!(vm.registers.l1, 0xffff);
+}
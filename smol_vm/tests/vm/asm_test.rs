@@ -0,0 +1,118 @@
+use smol_asm::{assemble, Options};
+use smol_vm::Vm;
+
+fn run(source: &str) -> Vm {
+    let file = assemble(source, &Options::default()).unwrap();
+    let mut vm = Vm::default();
+    vm.load_file(file).unwrap();
+    vm.run().unwrap();
+    vm
+}
+
+#[test]
+pub fn it_runs_a_loop() {
+    let vm = run("\
+main:
+    sti r0 5
+loop:
+    add r1 r0
+    dec r0
+    bnz loop
+");
+
+    assert_eq!(vm.registers.r0, 0);
+    assert_eq!(vm.registers.r1, 15);
+}
+
+#[test]
+pub fn it_calls_functions() {
+    let vm = run("\
+double:
+    add r0 r0
+    ret
+main:
+    sti r0 3
+    call double
+    call double
+");
+
+    assert_eq!(vm.registers.r0, 12);
+    assert_eq!(vm.registers.sp, 0);
+}
+
+#[test]
+pub fn it_reads_initialised_variables() {
+    let vm = run("\
+---
+word 3 \"abc\"
+---
+main:
+    sv word
+    sti r1 2
+    ldm [vp + r1] r0
+    ldm [vp] r2
+");
+
+    assert_eq!(vm.registers.r0, b'c');
+    assert_eq!(vm.registers.r2, b'a');
+}
+
+#[cfg(unix)]
+#[test]
+pub fn it_checks_syscall_errors() {
+    // Writing to a closed file descriptor fails with EBADF
+    let vm = run("\
+---
+msg 2 \"hi\"
+---
+main:
+    sv msg
+    stl l0 vp
+    stil l1 2
+    sti r1 255
+    sti r0 1
+    syscall
+    berr failed
+    sti r2 1
+    jmp end
+failed:
+    sti r2 2
+end:
+");
+
+    assert_eq!(vm.registers.r2, 2);
+    assert_eq!(vm.registers.l0, libc::EBADF as u16);
+}
+
+#[test]
+pub fn it_runs_alu_mnemonics() {
+    let vm = run("\
+main:
+    sti r0 12
+    sti r1 10
+    sub r0 r1
+    sti r2 12
+    andi r2 10
+    sti r3 12
+    ori r3 3
+    sti r4 12
+    xor r4 r3
+    not r5
+    inc r6
+    dec r7
+    stil l0 1000
+    subil l0 1001
+    stil l1 255
+    notl l1
+");
+
+    assert_eq!(vm.registers.r0, 2);
+    assert_eq!(vm.registers.r2, 0b1000);
+    assert_eq!(vm.registers.r3, 0b1111);
+    assert_eq!(vm.registers.r4, 0b0011);
+    assert_eq!(vm.registers.r5, 0xFF);
+    assert_eq!(vm.registers.r6, 1);
+    assert_eq!(vm.registers.r7, 0xFF);
+    assert_eq!(vm.registers.l0, 0xFFFF);
+    assert_eq!(vm.registers.l1, 0xFF00);
+}
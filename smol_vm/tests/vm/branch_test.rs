@@ -1,4 +1,4 @@
-use smol_vm::Vm;
+use smol_vm::{Vm, FG_ERROR};
 
 #[test]
 pub fn it_jumps_to_end_of_program() {
@@ -16,7 +16,7 @@ pub fn it_jumps_to_end_of_program() {
         // Registers r0 and r1
         0b0001_0000,
     ];
-    vm.run();
+    vm.run().unwrap();
 
     assert_eq!(vm.registers.r0, 1);
     assert_eq!(vm.registers.ic, 5);
@@ -44,7 +44,7 @@ pub fn it_runs_without_jump() {
         // Registers r0 and r1
         0b0001_0000,
     ];
-    vm.run();
+    vm.run().unwrap();
 
     assert_eq!(vm.registers.r0, 3);
     assert_eq!(vm.registers.ic, 7);
@@ -72,7 +72,7 @@ pub fn it_branches_if_equal() {
         // Registers r0 and r1
         0b0001_0000,
     ];
-    vm.run();
+    vm.run().unwrap();
 
     assert_eq!(vm.registers.r0, 1);
     assert_eq!(vm.registers.ic, 7);
@@ -100,7 +100,7 @@ pub fn it_branches_if_not_equal() {
         // Registers r0 and r1
         0b0001_0000,
     ];
-    vm.run();
+    vm.run().unwrap();
 
     assert_eq!(vm.registers.r0, 1);
     assert_eq!(vm.registers.ic, 7);
@@ -128,7 +128,7 @@ pub fn it_branches_if_greater_than() {
         // Registers r0 and r1
         0b0001_0000,
     ];
-    vm.run();
+    vm.run().unwrap();
 
     assert_eq!(vm.registers.r0, 1);
     assert_eq!(vm.registers.ic, 7);
@@ -156,8 +156,216 @@ pub fn it_branches_if_less_than() {
         // Registers r0 and r1
         0b0001_0000,
     ];
-    vm.run();
+    vm.run().unwrap();
 
     assert_eq!(vm.registers.r0, 1);
     assert_eq!(vm.registers.ic, 7);
 }
+
+#[test]
+pub fn it_adds_with_carry_across_registers() {
+    let mut vm = Vm::default();
+    // r1:r0 = 0x01ff, r3:r2 = 0x0001
+    vm.registers.r0 = 0xff;
+    vm.registers.r1 = 0x01;
+    vm.registers.r2 = 0x01;
+    vm.registers.r3 = 0x00;
+    vm.instructions.instructions = vec![
+        // ALU Add from Register
+        0b00_000_0_0_0,
+        // Registers r0 and r2
+        0b0010_0000,
+        // Branch if not carry
+        0b11_010_0_0_1,
+        // 16bit 7
+        7,
+        0,
+        // ALU Incerement from Register
+        0b00_111_0_0_0,
+        // Register r1
+        0b0000_0001,
+        // ALU Add from Register
+        0b00_000_0_0_0,
+        // Registers r1 and r3
+        0b0011_0001,
+    ];
+    vm.run().unwrap();
+
+    assert_eq!(vm.registers.r0, 0x00);
+    assert_eq!(vm.registers.r1, 0x02);
+}
+
+#[test]
+pub fn it_branches_if_zero() {
+    let mut vm = Vm::default();
+    vm.registers.r0 = 1;
+    vm.registers.r1 = 5;
+    vm.instructions.instructions = vec![
+        // ALU Decrement from Register
+        0b00_111_1_0_0,
+        // Register r0
+        0b0000_0000,
+        // Branch if zero
+        0b11_001_0_0_1,
+        // 16bit 7 (end of program)
+        7,
+        0,
+        // ALU Add from Register
+        0b00_000_0_0_0,
+        // Registers r1 and r1
+        0b0001_0001,
+    ];
+    vm.run().unwrap();
+
+    assert_eq!(vm.registers.r1, 5);
+    assert_eq!(vm.registers.ic, 7);
+}
+
+#[test]
+pub fn it_branches_if_sign() {
+    let mut vm = Vm::default();
+    vm.registers.r1 = 5;
+    vm.instructions.instructions = vec![
+        // ALU Decrement from Register
+        0b00_111_1_0_0,
+        // Register r0
+        0b0000_0000,
+        // Branch if sign
+        0b11_001_1_0_0,
+        // 16bit 7 (end of program)
+        7,
+        0,
+        // ALU Add from Register
+        0b00_000_0_0_0,
+        // Registers r1 and r1
+        0b0001_0001,
+    ];
+    vm.run().unwrap();
+
+    assert_eq!(vm.registers.r0, 255);
+    assert_eq!(vm.registers.r1, 5);
+}
+
+#[test]
+pub fn it_branches_if_syscall_failed() {
+    let mut vm = Vm::default();
+    vm.registers.fg = FG_ERROR;
+    vm.instructions.instructions = vec![
+        // Branch if not error
+        0b11_010_1_0_1,
+        // 16bit 8 (end of program)
+        8,
+        0,
+        // Branch if error
+        0b11_001_1_0_1,
+        // 16bit 8 (end of program)
+        8,
+        0,
+        // ALU Add from Register
+        0b00_000_0_0_0,
+        // Registers r1 and r1
+        0b0001_0001,
+    ];
+    vm.registers.r1 = 5;
+    vm.run().unwrap();
+
+    assert_eq!(vm.registers.ic, 8);
+    assert_eq!(vm.registers.r1, 5);
+}
+
+#[test]
+pub fn it_runs_without_overflow_branch() {
+    let mut vm = Vm::default();
+    vm.registers.r1 = 5;
+    vm.instructions.instructions = vec![
+        // ALU Incerement from Register
+        0b00_111_0_0_0,
+        // Register r0
+        0b0000_0000,
+        // Branch if overflow
+        0b11_001_0_1_1,
+        // 16bit 7 (end of program)
+        7,
+        0,
+        // ALU Add from Register
+        0b00_000_0_0_0,
+        // Registers r1 and r1
+        0b0001_0001,
+    ];
+    vm.run().unwrap();
+
+    assert_eq!(vm.registers.r1, 10);
+}
+
+#[test]
+pub fn it_jumps_to_register() {
+    let mut vm = Vm::default();
+    vm.registers.r0 = 1;
+    vm.registers.r1 = 2;
+    vm.registers.l0 = 4;
+    vm.instructions.instructions = vec![
+        // Jump to address in register
+        0b11_000_0_0_1,
+        // Register l0
+        0b0000_1001,
+        // ALU Add from Register
+        0b00_000_0_0_0,
+        // Registers r0 and r1
+        0b0001_0000,
+        // ALU Add from Register
+        0b00_000_0_0_0,
+        // Registers r1 and r1
+        0b0001_0001,
+    ];
+    vm.run().unwrap();
+
+    assert_eq!(vm.registers.r0, 1);
+    assert_eq!(vm.registers.r1, 4);
+}
+
+#[test]
+pub fn it_calls_register_and_returns() {
+    let mut vm = Vm::default();
+    vm.registers.r0 = 1;
+    vm.registers.r1 = 2;
+    vm.registers.l1 = 7;
+    vm.instructions.instructions = vec![
+        // Call address in register
+        0b11_101_0_0_1,
+        // Register l1
+        0b0000_1010,
+        // Jump to address 10 (end of program)
+        0b11_000_0_0_0,
+        10,
+        0,
+        // ALU Add from Register (skipped)
+        0b00_000_0_0_0,
+        // Registers r0 and r1
+        0b0001_0000,
+        // func: ALU Add from Register
+        0b00_000_0_0_0,
+        // Registers r1 and r1
+        0b0001_0001,
+        // Return from call
+        0b11_110_0_0_0,
+    ];
+    vm.run().unwrap();
+
+    assert_eq!(vm.registers.r0, 1);
+    assert_eq!(vm.registers.r1, 4);
+    assert_eq!(vm.registers.ic, 10);
+}
+
+#[test]
+pub fn it_jumps_to_itself() {
+    let mut vm = Vm::default();
+    vm.instructions.instructions = vec![
+        // Jump to address 0
+        0b11_000_0_0_0,
+        0,
+        0,
+    ];
+
+    assert_eq!(vm.run_for(5), Ok(smol_vm::ExitReason::InstructionLimit));
+    assert_eq!(vm.registers.ic, 0);
+}
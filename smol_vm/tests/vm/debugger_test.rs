@@ -0,0 +1,133 @@
+use smol_file::Symbol;
+use smol_vm::{debugger::Debugger, ExitReason, Vm, IVT_START};
+
+fn call_program() -> Debugger {
+    let mut vm = Vm::default();
+    vm.registers.r0 = 1;
+    vm.registers.r1 = 2;
+    vm.instructions.instructions = vec![
+        // Call func
+        0b11_101_0_0_0,
+        8,
+        0,
+        // ALU Add from Register
+        0b00_000_0_0_0,
+        // Registers r0 and r1
+        0b0001_0000,
+        // Jump to address 11 (end of program)
+        0b11_000_0_0_0,
+        11,
+        0,
+        // func: ALU Add from Register
+        0b00_000_0_0_0,
+        // Registers r0 and r1
+        0b0001_0000,
+        // Return from call
+        0b11_110_0_0_0,
+    ];
+
+    let symbols = vec![
+        Symbol {
+            name: "main".into(),
+            address: 0,
+        },
+        Symbol {
+            name: "func".into(),
+            address: 8,
+        },
+    ];
+    Debugger::new(vm, symbols)
+}
+
+#[test]
+pub fn it_stops_at_breakpoint() {
+    let mut dbg = call_program();
+    dbg.add_breakpoint(dbg.resolve("func").unwrap());
+
+    assert_eq!(dbg.resume(), Ok(ExitReason::Predicate));
+    assert_eq!(dbg.vm.registers.ic, 8);
+    assert_eq!(dbg.backtrace(), vec![8, 3]);
+
+    dbg.step().unwrap();
+    dbg.step().unwrap();
+    assert_eq!(dbg.vm.registers.ic, 3);
+    assert_eq!(dbg.backtrace(), vec![3]);
+
+    assert_eq!(dbg.resume(), Ok(ExitReason::Finished));
+    assert_eq!(dbg.vm.registers.r0, 5);
+}
+
+#[test]
+pub fn it_runs_repl_commands() {
+    let mut dbg = call_program();
+    let input = "b func\nc\nbt\nr\nx 0 4\nc\nq\n";
+    let mut output: Vec<u8> = Vec::new();
+    dbg.repl(input.as_bytes(), &mut output).unwrap();
+
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("Breakpoint at 0x0008 <func+0>"));
+    assert!(output.contains("#1 0x0003 <main+3>"));
+    assert!(output.contains("r0 0x01"));
+    assert!(output.contains("0000: 03 00 00 00"));
+    assert!(output.contains("Program finished"));
+}
+
+/// Output that fails like a closed pipe
+struct BrokenPipe;
+
+impl std::io::Write for BrokenPipe {
+    fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+        Err(std::io::ErrorKind::BrokenPipe.into())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+pub fn it_returns_repl_io_errors() {
+    let mut dbg = call_program();
+    let err = dbg.repl("r\n".as_bytes(), BrokenPipe).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::BrokenPipe);
+}
+
+#[test]
+pub fn it_tracks_interrupt_frames() {
+    let mut vm = Vm::default();
+    let vector = IVT_START as usize;
+    vm.stack.memory_mut()[vector..vector + 2].copy_from_slice(&8_u16.to_le_bytes());
+    vm.instructions.instructions = vec![
+        // Enable interrupts
+        0b11_111_0_0_1,
+        // Call func
+        0b11_101_0_0_0,
+        7,
+        0,
+        // Jump to address 9 (end of program)
+        0b11_000_0_0_0,
+        9,
+        0,
+        // func: Return from call
+        0b11_110_0_0_0,
+        // handler: Return from interrupt
+        0b11_111_0_0_0,
+    ];
+    let mut dbg = Debugger::new(vm, Vec::new());
+
+    dbg.step().unwrap();
+    dbg.vm.raise_interrupt(0);
+
+    // The interrupt is taken instead of the call
+    dbg.step().unwrap();
+    assert_eq!(dbg.backtrace(), vec![8, 1]);
+
+    dbg.step().unwrap();
+    assert_eq!(dbg.backtrace(), vec![1]);
+
+    dbg.step().unwrap();
+    assert_eq!(dbg.backtrace(), vec![7, 4]);
+
+    assert_eq!(dbg.resume(), Ok(ExitReason::Finished));
+    assert_eq!(dbg.backtrace(), vec![9]);
+}
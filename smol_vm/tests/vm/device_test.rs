@@ -0,0 +1,243 @@
+use std::{cell::RefCell, io::Write, rc::Rc};
+
+use smol_vm::{
+    device::{Console, Device, MapError, Random, Timer},
+    ExitReason, Vm, VmError, INTERRUPT_LINES, IVT_START,
+};
+
+/// Output of the console that can still be read after the console is mapped
+#[derive(Clone, Default)]
+struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedOutput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Device that remembers the last written byte
+struct Latch {
+    value: u8,
+}
+
+impl Device for Latch {
+    fn read(&mut self, offset: u16) -> u8 {
+        self.value.wrapping_add(offset as u8)
+    }
+
+    fn write(&mut self, _offset: u16, value: u8) {
+        self.value = value;
+    }
+}
+
+#[test]
+pub fn it_writes_to_console() {
+    let mut vm = Vm::default();
+    let output = SharedOutput::default();
+    vm.bus
+        .map(0xFF00, 2, Console::new(&b""[..], output.clone()))
+        .unwrap();
+    vm.instructions.instructions = vec![
+        // STM  a/16 i/8  - Store 'h' into the console
+        0b01_00_1_1_0_0,
+        // address of 0xFF00 in 16 bit little endian
+        0x00,
+        0xFF,
+        b'h',
+        // STM  a/16 i/8  - Store 'i' into the console
+        0b01_00_1_1_0_0,
+        0x00,
+        0xFF,
+        b'i',
+    ];
+    vm.run().unwrap();
+
+    assert_eq!(output.0.borrow().as_slice(), b"hi");
+    // Nothing is written to the memory behind the device
+    assert_eq!(vm.stack.memory()[0xFF00], 0);
+}
+
+#[test]
+pub fn it_maps_the_given_console() {
+    let mut vm = Vm::default();
+    vm.map_default_devices(Console::new(&b"x"[..], std::io::sink()));
+    vm.instructions.instructions = vec![
+        // LDM  a/16 r/8  - Load the next input byte
+        0b01_01_1_0_0_0,
+        0x00,
+        0xFF,
+        // Register r0
+        0b0000_0000,
+    ];
+    vm.run().unwrap();
+
+    assert_eq!(vm.registers.r0, b'x');
+}
+
+#[test]
+pub fn it_reads_from_console() {
+    let mut vm = Vm::default();
+    vm.bus
+        .map(0xFF00, 2, Console::new(&b"a"[..], std::io::sink()))
+        .unwrap();
+    vm.instructions.instructions = vec![
+        // LDM  a/16 r/8  - Load the next input byte
+        0b01_01_1_0_0_0,
+        0x00,
+        0xFF,
+        // Register r0
+        0b0000_0000,
+        // LDM  a/16 r/8  - Load the end of input
+        0b01_01_1_0_0_0,
+        0x00,
+        0xFF,
+        // Register r1
+        0b0000_0001,
+        // LDM  a/16 r/8  - Load the status
+        0b01_01_1_0_0_0,
+        0x01,
+        0xFF,
+        // Register r2
+        0b0000_0010,
+    ];
+    vm.run().unwrap();
+
+    assert_eq!(vm.registers.r0, b'a');
+    assert_eq!(vm.registers.r1, 0);
+    assert_eq!(vm.registers.r2, 1);
+}
+
+#[test]
+pub fn it_accesses_custom_device_with_16b_values() {
+    let mut vm = Vm::default();
+    vm.bus.map(0x1000, 2, Latch { value: 0 }).unwrap();
+    vm.registers.l0 = 0x0205;
+    vm.instructions.instructions = vec![
+        // STR  a/16 r/16  - Store l0, the last byte written is 0x02
+        0b01_00_1_0_1_0,
+        0x00,
+        0x10,
+        // Register l0
+        0b0000_1001,
+        // LDML a/16 r/16 - Load 16-bit register from the device
+        0b01_01_1_0_1_0,
+        0x00,
+        0x10,
+        // Register l1
+        0b0000_1010,
+    ];
+    vm.run().unwrap();
+
+    assert_eq!(vm.registers.l1, 0x0302);
+}
+
+#[test]
+pub fn it_rejects_overlapping_devices() {
+    let mut vm = Vm::default();
+    vm.bus.map(0x1000, 4, Latch { value: 0 }).unwrap();
+
+    assert_eq!(
+        vm.bus.map(0x1003, 2, Latch { value: 0 }),
+        Err(MapError::Overlap {
+            start: 0x1003,
+            len: 2
+        })
+    );
+    assert_eq!(
+        vm.bus.map(0xFFFF, 2, Latch { value: 0 }),
+        Err(MapError::InvalidRange {
+            start: 0xFFFF,
+            len: 2
+        })
+    );
+    assert!(vm.bus.map(0x1004, 2, Latch { value: 0 }).is_ok());
+}
+
+#[test]
+pub fn it_raises_timer_interrupts() {
+    let mut vm = Vm::default();
+    vm.bus.map(0xFF10, 5, Timer::new(1).unwrap()).unwrap();
+    let vector = IVT_START as usize + 2;
+    vm.stack.memory_mut()[vector..vector + 2].copy_from_slice(&13_u16.to_le_bytes());
+    vm.instructions.instructions = vec![
+        // STML a/16 i/16 - Timer interval of 2 instructions
+        0b01_00_1_1_1_0,
+        0x10,
+        0xFF,
+        2,
+        0,
+        // STM  a/16 i/8  - Enable the timer
+        0b01_00_1_1_0_0,
+        0x12,
+        0xFF,
+        1,
+        // Enable interrupts
+        0b11_111_0_0_1,
+        // loop: Jump to loop
+        0b11_000_0_0_0,
+        10,
+        0,
+        // handler: ALU Incerement from Register
+        0b00_111_0_0_0,
+        // Register r0
+        0b0000_0000,
+        // Return from interrupt
+        0b11_111_0_0_0,
+    ];
+
+    assert_eq!(vm.run_for(20), Ok(ExitReason::InstructionLimit));
+    assert!(vm.registers.r0 >= 3);
+}
+
+/// Raises the same interrupt line after every instruction
+struct Raise(u8);
+
+impl Device for Raise {
+    fn read(&mut self, _offset: u16) -> u8 {
+        0
+    }
+
+    fn write(&mut self, _offset: u16, _value: u8) {}
+
+    fn tick(&mut self) -> Option<u8> {
+        Some(self.0)
+    }
+}
+
+#[test]
+pub fn it_rejects_invalid_interrupt_lines() {
+    assert!(Timer::new(INTERRUPT_LINES - 1).is_some());
+    assert!(Timer::new(INTERRUPT_LINES).is_none());
+
+    let mut vm = Vm::default();
+    vm.bus.map(0x1000, 1, Raise(INTERRUPT_LINES)).unwrap();
+    vm.instructions.instructions = vec![
+        // Enable interrupts
+        0b11_111_0_0_1,
+        // Disable interrupts
+        0b11_111_0_1_0,
+    ];
+
+    assert_eq!(
+        vm.step(),
+        Err(VmError::InvalidInterruptLine {
+            ic: 0,
+            line: INTERRUPT_LINES
+        })
+    );
+}
+
+#[test]
+pub fn it_generates_random_bytes() {
+    let mut first = Random::new(42);
+    let mut second = Random::new(42);
+    let bytes: Vec<u8> = (0..8).map(|_| first.read(0)).collect();
+
+    assert_eq!(bytes, (0..8).map(|_| second.read(0)).collect::<Vec<u8>>());
+    assert!(bytes.iter().any(|byte| *byte != bytes[0]));
+}
@@ -0,0 +1,300 @@
+use smol_file::Symbol;
+use smol_vm::{
+    disasm::{self, Operand},
+    Vm,
+};
+
+#[test]
+pub fn it_decodes_alu_instructions() {
+    let instrs = vec![
+        // ALU Add from Register
+        0b00_000_0_0_0,
+        // Registers r0 and r1
+        0b0001_0000,
+        // ALU EQR
+        0b00_110_1_0_0,
+        // Register r7
+        0b0000_0111,
+        50,
+        // ALU Decrement from Register
+        0b00_111_1_0_0,
+        // Register r 7
+        0b0000_0111,
+    ];
+
+    let decoded = disasm::disassemble(&instrs);
+    assert_eq!(decoded.len(), 3);
+    assert_eq!(decoded[0].mnemonic, "add");
+    assert_eq!(
+        decoded[0].operands,
+        vec![Operand::Register(0), Operand::Register(1)]
+    );
+    assert_eq!(decoded[1].address, 2);
+    assert_eq!(decoded[1].mnemonic, "eqi");
+    assert_eq!(
+        decoded[1].operands,
+        vec![Operand::Register(7), Operand::Immediate(50)]
+    );
+    assert_eq!(decoded[2].mnemonic, "dec");
+}
+
+#[test]
+pub fn it_decodes_extended_alu_instructions() {
+    let instrs = vec![
+        // ALU Multiply from Register
+        0b00_000_0_0_1,
+        // Registers r0 and r1
+        0b0001_0000,
+        // ALU Arithmetic shift right from 16-bit Immediate
+        0b00_101_1_1_1,
+        // Register l0
+        0b0000_1001,
+        3,
+        0,
+    ];
+
+    let decoded = disasm::disassemble(&instrs);
+    assert_eq!(decoded.len(), 2);
+    assert_eq!(decoded[0].mnemonic, "mul");
+    assert_eq!(decoded[1].mnemonic, "saril");
+    assert_eq!(
+        decoded[1].operands,
+        vec![Operand::Register(9), Operand::Immediate(3)]
+    );
+}
+
+#[test]
+pub fn it_decodes_load_store_instructions() {
+    let instrs = vec![
+        // STML a/16 i/16 - Store 16-bit immediate into memory
+        0b01_00_1_1_1_0,
+        0b00000000,
+        0b00000001,
+        0b00000010,
+        0b00000001,
+        // LDM  a/16 r/8  - Load register from memory
+        0b01_01_1_0_0_0,
+        0b00000000,
+        0b00000001,
+        0b0000_0010,
+    ];
+
+    let decoded = disasm::disassemble(&instrs);
+    assert_eq!(decoded[0].mnemonic, "stml");
+    assert_eq!(decoded[0].bytes.len(), 5);
+    assert_eq!(decoded[1].mnemonic, "ldm");
+    assert_eq!(
+        decoded[1].operands,
+        vec![Operand::Address(256), Operand::Register(2)]
+    );
+}
+
+#[test]
+pub fn it_formats_indirect_addresses() {
+    let instrs = vec![
+        // LDM  [vp + r1] r2
+        0b01_01_1_0_0_1,
+        0b0001_1000,
+        0b0000_0001,
+        0b0000_0010,
+        // STRL [sp - 2] l0
+        0b01_00_1_0_1_1,
+        0b0011_1110,
+        2,
+        0b0000_1001,
+    ];
+
+    let decoded = disasm::disassemble(&instrs);
+    assert_eq!(decoded.len(), 2);
+    assert_eq!(
+        disasm::format_instruction(&decoded[0], &[]),
+        "ldm [vp + r1] r2"
+    );
+    assert_eq!(
+        disasm::format_instruction(&decoded[1], &[]),
+        "strl [sp - 2] l0"
+    );
+}
+
+#[test]
+pub fn it_decodes_invalid_bytes() {
+    let instrs = vec![
+        // Load with a register target, which doesn't exist
+        0b01_01_0_0_0_0,
+        // Truncated jump
+        0b11_000_0_0_0,
+        5,
+    ];
+
+    let decoded = disasm::disassemble(&instrs);
+    assert_eq!(decoded.len(), 3);
+    assert!(decoded.iter().all(|instr| instr.mnemonic == ".byte"));
+}
+
+#[test]
+pub fn it_lists_instructions_with_labels() {
+    let instrs = vec![
+        // Call func
+        0b11_101_0_0_0,
+        7,
+        0,
+        // Branch if equal
+        0b11_001_0_0_0,
+        7,
+        0,
+        // Return from call
+        0b11_110_0_0_0,
+        // Syscall
+        0b11_101_1_1_1,
+    ];
+    let symbols = vec![Symbol {
+        name: "start".into(),
+        address: 0,
+    }];
+
+    let listing = disasm::listing(&instrs, 0, &symbols);
+    let expected = "\
+start:
+    0000: e8 07 00        call L_0007
+    0003: c8 07 00        be L_0007
+    0006: f0              ret
+L_0007:
+    0007: ef              syscall
+";
+    assert_eq!(listing, expected);
+}
+
+#[test]
+pub fn it_decodes_flag_branches() {
+    let instrs = vec![
+        // Branch if carry
+        0b11_001_0_1_0,
+        0,
+        0,
+        // Branch if not sign
+        0b11_010_1_0_0,
+        0,
+        0,
+        // Branch if error
+        0b11_001_1_0_1,
+        0,
+        0,
+        // Unused branch flag
+        0b11_001_1_1_1,
+    ];
+
+    let decoded = disasm::disassemble(&instrs);
+    assert_eq!(decoded[0].mnemonic, "bc");
+    assert_eq!(decoded[1].mnemonic, "bns");
+    assert_eq!(decoded[2].mnemonic, "berr");
+    assert_eq!(decoded[3].mnemonic, ".byte");
+}
+
+#[test]
+pub fn it_decodes_register_jumps() {
+    let instrs = vec![
+        // Jump to address in register
+        0b11_000_0_0_1,
+        // Register l0
+        0b0000_1001,
+        // Call address in register
+        0b11_101_0_0_1,
+        // Register l1
+        0b0000_1010,
+    ];
+
+    let decoded = disasm::disassemble(&instrs);
+    assert_eq!(decoded.len(), 2);
+    assert_eq!(decoded[0].mnemonic, "jmp");
+    assert_eq!(decoded[0].operands, vec![Operand::Register(9)]);
+    assert_eq!(decoded[1].mnemonic, "call");
+    assert_eq!(decoded[1].target(), None);
+}
+
+#[test]
+pub fn it_decodes_interrupt_instructions() {
+    let instrs = vec![0b11_111_0_0_1, 0b11_111_0_1_0, 0b11_111_0_0_0];
+
+    let decoded = disasm::disassemble(&instrs);
+    let mnemonics: Vec<&str> = decoded.iter().map(|instr| instr.mnemonic).collect();
+    assert_eq!(mnemonics, vec!["ei", "di", "reti"]);
+}
+
+#[test]
+pub fn it_decodes_alu_lengths_like_the_vm() {
+    for opcode in 0..=0b00_111_111u8 {
+        // Registers r0 and r1, the immediate is non-zero so division succeeds
+        let instrs = vec![opcode, 0b0001_0000, 1, 0, 0, 0];
+        let mut vm = Vm::default();
+        vm.instructions.instructions = instrs.clone();
+        vm.registers.r1 = 1;
+
+        let decoded = disasm::decode(&instrs, 0);
+        match vm.step() {
+            Ok(_) => assert_eq!(
+                decoded.bytes.len(),
+                vm.registers.ic as usize,
+                "{opcode:#010b} decoded as {}",
+                decoded.mnemonic
+            ),
+            Err(_) => assert_eq!(decoded.mnemonic, ".byte", "{opcode:#010b}"),
+        }
+    }
+}
+
+#[test]
+pub fn it_skips_the_immediate_of_not() {
+    let instrs = vec![
+        // ALU not with the source bit set
+        0b00_101_1_0_0,
+        0b0000_0000,
+        5,
+        // ALU not 16-bit with the source bit set
+        0b00_101_1_1_0,
+        0b0000_1001,
+        5,
+        0,
+    ];
+
+    let decoded = disasm::disassemble(&instrs);
+    assert_eq!(decoded.len(), 2);
+    assert_eq!(decoded[0].mnemonic, "not");
+    assert_eq!(decoded[1].address, 3);
+    assert_eq!(decoded[1].mnemonic, "notl");
+    assert_eq!(decoded[1].operands, vec![Operand::Register(0b1001)]);
+}
+
+#[test]
+pub fn it_decodes_push_and_pop() {
+    let instrs = vec![
+        // Push 8-bit register r1
+        0b10_00_0_0_00,
+        0b0000_0001,
+        // Push 16-bit register l0
+        0b10_00_0_1_00,
+        0b0000_1001,
+        // Push 8-bit immediate
+        0b10_00_1_0_00,
+        7,
+        // Push 16-bit immediate
+        0b10_00_1_1_00,
+        0x34,
+        0x12,
+        // Pop 8-bit register r1
+        0b10_01_0_0_00,
+        0b0000_0001,
+        // Pop 16-bit register l0
+        0b10_01_0_1_00,
+        0b0000_1001,
+    ];
+
+    let decoded = disasm::disassemble(&instrs);
+    let mnemonics: Vec<&str> = decoded.iter().map(|instr| instr.mnemonic).collect();
+    assert_eq!(
+        mnemonics,
+        ["push", "pushl", "pushi", "pushil", "pop", "popl"]
+    );
+    assert_eq!(decoded[1].operands, vec![Operand::Register(0b1001)]);
+    assert_eq!(decoded[3].operands, vec![Operand::Immediate(0x1234)]);
+    assert_eq!(decoded[5].address, 11);
+}
@@ -0,0 +1,189 @@
+use smol_vm::{ExitReason, Vm, VmError};
+
+#[test]
+pub fn it_finishes_empty_program() {
+    let mut vm = Vm::default();
+    assert_eq!(vm.run(), Ok(ExitReason::Finished));
+}
+
+#[test]
+pub fn it_fails_on_stack_underflow() {
+    let mut vm = Vm::default();
+    vm.instructions.instructions = vec![
+        // POR  r/8  - Pop stack into register
+        0b10_01_0_0_00,
+        // Register r2
+        0b0000_0010,
+    ];
+
+    assert_eq!(
+        vm.run(),
+        Err(VmError::StackUnderflow {
+            ic: 0,
+            opcode: 0b10_01_0_0_00
+        })
+    );
+    assert_eq!(vm.registers.sp, 0);
+}
+
+#[test]
+pub fn it_fails_on_truncated_instruction() {
+    let mut vm = Vm::default();
+    vm.instructions.instructions = vec![
+        // ALU Add from Register
+        0b00_000_0_0_0,
+        // Registers r0 and r1
+        0b0001_0000,
+        // Jump to address, but the address is missing
+        0b11_000_0_0_0,
+        5,
+    ];
+
+    assert_eq!(
+        vm.run(),
+        Err(VmError::InstructionOutOfRange {
+            ic: 2,
+            opcode: Some(0b11_000_0_0_0)
+        })
+    );
+}
+
+#[test]
+pub fn it_fails_past_the_last_address() {
+    let mut vm = Vm::default();
+    // ALU Increment from Register in the last two bytes of the address space
+    vm.instructions.instructions = vec![0; 0x10000];
+    vm.instructions.instructions[0xFFFE] = 0b00_111_0_0_0;
+    vm.registers.ic = 0xFFFE;
+
+    assert_eq!(
+        vm.step(),
+        Err(VmError::InstructionOutOfRange {
+            ic: 0xFFFE,
+            opcode: Some(0b00_111_0_0_0)
+        })
+    );
+}
+
+#[test]
+pub fn it_fails_on_jump_out_of_range() {
+    let mut vm = Vm::default();
+    vm.instructions.instructions = vec![
+        // Jump to address
+        0b11_000_0_0_0,
+        // 16bit 100
+        100,
+        0,
+    ];
+
+    assert_eq!(
+        vm.run(),
+        Err(VmError::InstructionOutOfRange {
+            ic: 100,
+            opcode: None
+        })
+    );
+}
+
+#[test]
+pub fn it_fails_on_invalid_load() {
+    let mut vm = Vm::default();
+    vm.instructions.instructions = vec![
+        // Load with a register target, which doesn't exist
+        0b01_01_0_0_0_0,
+        0,
+        0,
+        0,
+    ];
+
+    let err = vm.run().unwrap_err();
+    assert_eq!(
+        err,
+        VmError::InvalidOpcode {
+            ic: 0,
+            opcode: 0b01_01_0_0_0_0
+        }
+    );
+    assert_eq!(err.ic(), 0);
+    assert_eq!(err.opcode(), Some(0b01_01_0_0_0_0));
+}
+
+#[test]
+pub fn it_fails_on_unimplemented_syscall() {
+    let mut vm = Vm::default();
+    vm.registers.r0 = 200;
+    vm.instructions.instructions = vec![
+        // Syscall
+        0b11_101_1_1_1,
+    ];
+
+    assert_eq!(
+        vm.run(),
+        Err(VmError::UnimplementedSyscall {
+            ic: 0,
+            opcode: 0b11_101_1_1_1,
+            id: 200
+        })
+    );
+}
+
+#[test]
+pub fn it_fails_on_division_by_zero() {
+    let mut vm = Vm::default();
+    vm.registers.r0 = 10;
+    vm.instructions.instructions = vec![
+        // ALU Modulo from Register
+        0b00_010_0_0_1,
+        // Registers r0 and r1
+        0b0001_0000,
+    ];
+
+    assert_eq!(
+        vm.run(),
+        Err(VmError::DivisionByZero {
+            ic: 0,
+            opcode: 0b00_010_0_0_1
+        })
+    );
+    assert_eq!(vm.registers.r0, 10);
+}
+
+#[test]
+pub fn it_fails_on_invalid_jump() {
+    let mut vm = Vm::default();
+    vm.instructions.instructions = vec![
+        // Jump with unused low bits
+        0b11_000_0_1_0,
+        0,
+        0,
+    ];
+
+    assert_eq!(
+        vm.run(),
+        Err(VmError::InvalidOpcode {
+            ic: 0,
+            opcode: 0b11_000_0_1_0
+        })
+    );
+}
+
+#[test]
+pub fn it_fails_on_invalid_addressing_mode() {
+    let mut vm = Vm::default();
+    vm.instructions.instructions = vec![
+        // LDM  with an unused addressing mode
+        0b01_01_1_0_0_1,
+        0b0100_1001,
+        0,
+        // Register r2
+        0b0000_0010,
+    ];
+
+    assert_eq!(
+        vm.run(),
+        Err(VmError::InvalidOpcode {
+            ic: 0,
+            opcode: 0b01_01_1_0_0_1
+        })
+    );
+}
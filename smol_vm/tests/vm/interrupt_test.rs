@@ -0,0 +1,88 @@
+use smol_vm::{ExitReason, Vm, FG_INTERRUPT, IVT_START};
+
+/// Main increments r0 in a loop, the handler of line 2 increments r1
+fn interrupt_program() -> Vm {
+    let mut vm = Vm::default();
+    let vector = IVT_START as usize + 2 * 2;
+    vm.stack.memory_mut()[vector..vector + 2].copy_from_slice(&6_u16.to_le_bytes());
+    vm.instructions.instructions = vec![
+        // Enable interrupts
+        0b11_111_0_0_1,
+        // loop: ALU Incerement from Register
+        0b00_111_0_0_0,
+        // Register r0
+        0b0000_0000,
+        // Jump to loop
+        0b11_000_0_0_0,
+        1,
+        0,
+        // handler: ALU Incerement from Register
+        0b00_111_0_0_0,
+        // Register r1
+        0b0000_0001,
+        // Return from interrupt
+        0b11_111_0_0_0,
+    ];
+    vm
+}
+
+#[test]
+pub fn it_handles_raised_interrupt() {
+    let mut vm = interrupt_program();
+    assert_eq!(vm.run_for(3), Ok(ExitReason::InstructionLimit));
+    assert_eq!(vm.registers.r0, 1);
+    assert_eq!(vm.registers.ic, 1);
+
+    vm.raise_interrupt(2);
+    assert_eq!(vm.pending_interrupts(), 0b100);
+
+    // Enter the handler
+    vm.step().unwrap();
+    assert_eq!(vm.registers.ic, 6);
+    assert_eq!(vm.pending_interrupts(), 0);
+    assert_eq!(vm.registers.sp, 4);
+    // Interrupts are disabled in the handler
+    assert_eq!(vm.registers.fg & FG_INTERRUPT, 0);
+
+    // Increment and return
+    vm.run_for(2).unwrap();
+    assert_eq!(vm.registers.r1, 1);
+    assert_eq!(vm.registers.ic, 1);
+    assert_eq!(vm.registers.sp, 0);
+    assert_ne!(vm.registers.fg & FG_INTERRUPT, 0);
+}
+
+#[test]
+pub fn it_waits_until_interrupts_are_enabled() {
+    let mut vm = interrupt_program();
+    vm.raise_interrupt(2);
+
+    // Enable interrupts
+    vm.step().unwrap();
+    assert_eq!(vm.registers.ic, 1);
+    assert_eq!(vm.pending_interrupts(), 0b100);
+
+    vm.step().unwrap();
+    assert_eq!(vm.registers.ic, 6);
+}
+
+#[test]
+pub fn it_disables_interrupts() {
+    let mut vm = Vm::default();
+    vm.instructions.instructions = vec![
+        // Enable interrupts
+        0b11_111_0_0_1,
+        // Disable interrupts
+        0b11_111_0_1_0,
+        // ALU Incerement from Register
+        0b00_111_0_0_0,
+        // Register r0
+        0b0000_0000,
+    ];
+    vm.run_for(2).unwrap();
+    vm.raise_interrupt(0);
+    vm.run().unwrap();
+
+    assert_eq!(vm.registers.r0, 1);
+    assert_eq!(vm.pending_interrupts(), 0b1);
+}
@@ -0,0 +1,112 @@
+use smol_file::{memory::VARIABLE_SIZE, SmolFile, Storage, StorageItem};
+use smol_vm::{layout::Layout, LoadError, Vm};
+
+#[test]
+pub fn it_runs_file_from_bytes() {
+    let file = SmolFile {
+        storage: Storage {
+            total_size: 6,
+            items: vec![StorageItem {
+                size: 2,
+                offset: 0,
+                init_data: Some(vec![7, 9]),
+            }],
+        },
+        main_start: 0,
+        instructions: vec![
+            // Stack load variable immediate 8bit
+            0b10_10_1_0_00,
+            // Value of 0
+            0,
+            // LDM  [r/16 + i/8] r/8  - Load register from register address plus offset
+            0b01_01_1_0_0_1,
+            // Mode [base + imm], base vp
+            0b0010_1000,
+            1,
+            // Register r2
+            0b0000_0010,
+        ],
+    };
+
+    let bytes = file.to_bytes().unwrap();
+    let mut vm = Vm::default();
+    vm.load_file(SmolFile::from_bytes(&bytes).unwrap()).unwrap();
+    vm.run().unwrap();
+
+    assert_eq!(vm.stack.variable()[..2], [7, 9]);
+    assert_eq!(vm.registers.r2, 9);
+}
+
+fn storage_file(items: Vec<StorageItem>) -> SmolFile {
+    SmolFile {
+        storage: Storage {
+            total_size: 0,
+            items,
+        },
+        main_start: 0,
+        instructions: vec![],
+    }
+}
+
+#[test]
+pub fn it_rejects_storage_outside_of_the_layout() {
+    // Leaves 256 bytes for the variables
+    let layout = Layout::new(0xEF00, 0x0F00).unwrap();
+    let mut vm = Vm::with_layout(layout);
+    let file = storage_file(vec![
+        StorageItem {
+            size: 56,
+            offset: 200,
+            init_data: None,
+        },
+        StorageItem {
+            size: 100,
+            offset: 200,
+            init_data: Some(vec![1; 100]),
+        },
+    ]);
+
+    assert_eq!(
+        vm.load_file(file),
+        Err(LoadError::StorageOutOfRange { item: 1 })
+    );
+    assert!(vm.stack.variable().iter().all(|byte| *byte == 0));
+}
+
+#[test]
+pub fn it_rejects_mismatched_init_data() {
+    let mut vm = Vm::default();
+    let file = storage_file(vec![StorageItem {
+        size: 4,
+        offset: 0,
+        init_data: Some(vec![1, 2]),
+    }]);
+
+    assert_eq!(
+        vm.load_file(file),
+        Err(LoadError::InitDataMismatch {
+            item: 0,
+            size: 4,
+            len: 2
+        })
+    );
+}
+
+#[test]
+pub fn it_rejects_storage_over_the_devices() {
+    let mut vm = Vm::default();
+    let file = storage_file(vec![StorageItem {
+        size: 2,
+        offset: VARIABLE_SIZE - 1,
+        init_data: None,
+    }]);
+
+    assert_eq!(
+        vm.load_file(file),
+        Err(LoadError::StorageOutOfRange { item: 0 })
+    );
+    assert_eq!(
+        vm.stack.layout().variables().end,
+        smol_file::memory::IO_START as usize
+    );
+}
@@ -10,20 +10,21 @@ pub fn is_stores_register_in_register() {
         // Register r1 and r2
         0b0010_0001,
     ];
-    vm.run();
+    vm.run().unwrap();
     assert_eq!(vm.registers.r1, 3);
 }
 
+#[test]
 pub fn is_stores_16_bit_register_in_register() {
     let mut vm = Vm::default();
     vm.registers.l0 = 256;
     vm.instructions.instructions = vec![
         // STL  r/16 r/16 - Store 16-bit register into 16-bit register
-        0b01_00_0_1_1_0,
+        0b01_00_0_0_1_0,
         // Register l1 and l0
         0b1001_1010,
     ];
-    vm.run();
+    vm.run().unwrap();
     assert_eq!(vm.registers.l1, 256);
 }
 
@@ -37,10 +38,11 @@ pub fn is_stores_immediate_in_register() {
         0b0000_0001,
         3,
     ];
-    vm.run();
+    vm.run().unwrap();
     assert_eq!(vm.registers.r1, 3);
 }
 
+#[test]
 pub fn is_stores_16_bit_immediate_in_register() {
     let mut vm = Vm::default();
     vm.instructions.instructions = vec![
@@ -52,7 +54,7 @@ pub fn is_stores_16_bit_immediate_in_register() {
         0b00000000,
         0b00000001,
     ];
-    vm.run();
+    vm.run().unwrap();
     assert_eq!(vm.registers.l1, 256);
 }
 
@@ -67,7 +69,7 @@ pub fn is_stores_immediate_in_memory() {
         0b00000001,
         25,
     ];
-    vm.run();
+    vm.run().unwrap();
     assert_eq!(vm.stack.memory()[256], 25);
 }
 
@@ -84,7 +86,7 @@ pub fn is_stores_16b_immediate_in_memory() {
         0b00000010,
         0b00000001,
     ];
-    vm.run();
+    vm.run().unwrap();
 
     let val = u16::from_le_bytes([vm.stack.memory()[256], vm.stack.memory()[257]]);
     assert_eq!(val, 258);
@@ -103,7 +105,7 @@ pub fn is_stores_register_in_memory() {
         // register r2
         0b0000_0010,
     ];
-    vm.run();
+    vm.run().unwrap();
     assert_eq!(vm.stack.memory()[256], 5);
 }
 
@@ -120,7 +122,7 @@ pub fn is_stores_16b_bregister_in_memory() {
         // Register l1
         0b0000_1010,
     ];
-    vm.run();
+    vm.run().unwrap();
     let val = u16::from_le_bytes([vm.stack.memory()[256], vm.stack.memory()[257]]);
     assert_eq!(val, 258);
 }
@@ -138,7 +140,7 @@ pub fn it_loads_to_register() {
         // Register r2
         0b0000_0010,
     ];
-    vm.run();
+    vm.run().unwrap();
     assert_eq!(vm.registers.r2, 5);
 }
 
@@ -156,6 +158,176 @@ pub fn it_loads_to_16b_register() {
         // Register l1
         0b0000_1010,
     ];
-    vm.run();
+    vm.run().unwrap();
+    assert_eq!(vm.registers.l1, 258);
+}
+
+#[test]
+pub fn it_swaps_registers() {
+    let mut vm = Vm::default();
+    vm.registers.r1 = 3;
+    vm.registers.r2 = 7;
+    vm.instructions.instructions = vec![
+        // SWP  r/8  r/8  - Swap register with register
+        0b01_11_0_0_0_0,
+        // Register r1 and r2
+        0b0010_0001,
+    ];
+    vm.run().unwrap();
+    assert_eq!(vm.registers.r1, 7);
+    assert_eq!(vm.registers.r2, 3);
+    assert_eq!(vm.registers.ic, 2);
+}
+
+#[test]
+pub fn it_swaps_16b_registers() {
+    let mut vm = Vm::default();
+    vm.registers.l0 = 256;
+    vm.registers.l1 = 1000;
+    vm.instructions.instructions = vec![
+        // SWPL r/16 r/16 - Swap 16-bit register with 16-bit register
+        0b01_11_0_0_1_0,
+        // Register l1 and l0
+        0b1001_1010,
+    ];
+    vm.run().unwrap();
+    assert_eq!(vm.registers.l0, 1000);
+    assert_eq!(vm.registers.l1, 256);
+}
+
+#[test]
+pub fn it_swaps_register_with_memory() {
+    let mut vm = Vm::default();
+    vm.registers.r2 = 5;
+    vm.stack.memory_mut()[256] = 9;
+    vm.instructions.instructions = vec![
+        // SWPM a/16 r/8  - Swap register with memory
+        0b01_11_1_0_0_0,
+        // address of 256 in 16 bit little endian
+        0b00000000,
+        0b00000001,
+        // Register r2
+        0b0000_0010,
+    ];
+    vm.run().unwrap();
+    assert_eq!(vm.registers.r2, 9);
+    assert_eq!(vm.stack.memory()[256], 5);
+    assert_eq!(vm.stack.memory()[257], 0);
+    assert_eq!(vm.registers.ic, 4);
+}
+
+#[test]
+pub fn it_swaps_16b_register_with_memory() {
+    let mut vm = Vm::default();
+    vm.registers.l1 = 258;
+    vm.stack.memory_mut()[256] = 0b00000011;
+    vm.stack.memory_mut()[257] = 0b00000100;
+    vm.instructions.instructions = vec![
+        // SWPML a/16 r/16 - Swap 16-bit register with memory
+        0b01_11_1_0_1_0,
+        // address of 256 in 16 bit little endian
+        0b00000000,
+        0b00000001,
+        // Register l1
+        0b0000_1010,
+    ];
+    vm.run().unwrap();
+    let val = u16::from_le_bytes([vm.stack.memory()[256], vm.stack.memory()[257]]);
+    assert_eq!(val, 258);
+    assert_eq!(vm.registers.l1, 0x0403);
+}
+
+#[test]
+pub fn it_loads_from_register_address() {
+    let mut vm = Vm::default();
+    vm.registers.l0 = 256;
+    vm.stack.memory_mut()[256] = 5;
+    vm.instructions.instructions = vec![
+        // LDM  [r/16] r/8  - Load register from address in register
+        0b01_01_1_0_0_1,
+        // Mode [base], base l0
+        0b0000_1001,
+        0,
+        // Register r2
+        0b0000_0010,
+    ];
+    vm.run().unwrap();
+    assert_eq!(vm.registers.r2, 5);
+    assert_eq!(vm.registers.ic, 4);
+}
+
+#[test]
+pub fn it_loads_from_indexed_address() {
+    let mut vm = Vm::default();
+    vm.registers.vp = 256;
+    vm.registers.r1 = 3;
+    vm.stack.memory_mut()[259] = 0b00000010;
+    vm.stack.memory_mut()[260] = 0b00000001;
+    vm.instructions.instructions = vec![
+        // LDML [r/16 + r] r/16 - Load 16-bit register from indexed address
+        0b01_01_1_0_1_1,
+        // Mode [base + index], base vp
+        0b0001_1000,
+        // Index r1
+        0b0000_0001,
+        // Register l1
+        0b0000_1010,
+    ];
+    vm.run().unwrap();
     assert_eq!(vm.registers.l1, 258);
 }
+
+#[test]
+pub fn it_stores_immediate_at_offset_address() {
+    let mut vm = Vm::default();
+    vm.registers.l0 = 256;
+    vm.instructions.instructions = vec![
+        // STM  [r/16 + i/8] i/8  - Store immediate at register address plus offset
+        0b01_00_1_1_0_1,
+        // Mode [base + imm], base l0
+        0b0010_1001,
+        10,
+        7,
+    ];
+    vm.run().unwrap();
+    assert_eq!(vm.stack.memory()[266], 7);
+}
+
+#[test]
+pub fn it_stores_16b_register_below_sp() {
+    let mut vm = Vm::default();
+    vm.registers.sp = 258;
+    vm.registers.l0 = 1000;
+    vm.instructions.instructions = vec![
+        // STRL [r/16 - i/8] r/16 - Store 16-bit register at register address minus offset
+        0b01_00_1_0_1_1,
+        // Mode [base - imm], base sp
+        0b0011_1110,
+        2,
+        // Register l0
+        0b0000_1001,
+    ];
+    vm.run().unwrap();
+    let val = u16::from_le_bytes([vm.stack.memory()[256], vm.stack.memory()[257]]);
+    assert_eq!(val, 1000);
+}
+
+#[test]
+pub fn it_swaps_register_with_indirect_memory() {
+    let mut vm = Vm::default();
+    vm.registers.l1 = 256;
+    vm.registers.r2 = 5;
+    vm.stack.memory_mut()[257] = 9;
+    vm.instructions.instructions = vec![
+        // SWPM [r/16 + i/8] r/8  - Swap register with memory at register address plus offset
+        0b01_11_1_0_0_1,
+        // Mode [base + imm], base l1
+        0b0010_1010,
+        1,
+        // Register r2
+        0b0000_0010,
+    ];
+    vm.run().unwrap();
+    assert_eq!(vm.registers.r2, 9);
+    assert_eq!(vm.stack.memory()[257], 5);
+}
@@ -1,4 +1,14 @@
 mod alu_eq_test;
+mod asm_test;
 mod branch_test;
+mod debugger_test;
+mod device_test;
+mod disasm_test;
+mod error_test;
+mod interrupt_test;
+mod load_file_test;
 mod load_store_test;
+mod segment_test;
 mod stack_test;
+mod step_test;
+mod syscall_test;
@@ -0,0 +1,131 @@
+use smol_vm::layout::Layout;
+use smol_vm::{Vm, VmError};
+
+#[test]
+pub fn it_rejects_layout_without_variables() {
+    assert_eq!(Layout::new(0x8000, 0x8000), None);
+    assert_eq!(Layout::new(0xF000, 0x1000), None);
+    // Variables end at the device registers
+    assert_eq!(Layout::new(0xF000, 0x0F00), None);
+    assert!(Layout::new(0x1000, 0x100).is_some());
+}
+
+#[test]
+pub fn it_overflows_the_stack_segment() {
+    let mut vm = Vm::with_layout(Layout::new(2, 16).unwrap());
+    vm.instructions.instructions = vec![
+        // PUI  i/8  - Push immidiate onto stack
+        0b10_00_1_0_00,
+        1,
+        // PURL i/16 - Push 16-bit immidiate onto stack
+        0b10_00_1_1_00,
+        2,
+        0,
+    ];
+
+    assert_eq!(
+        vm.run(),
+        Err(VmError::StackOverflow {
+            ic: 2,
+            opcode: 0b10_00_1_1_00
+        })
+    );
+    assert_eq!(vm.registers.sp, 1);
+    assert_eq!(vm.stack.memory()[2], 0);
+}
+
+#[test]
+pub fn it_faults_on_guard_region_access() {
+    let mut vm = Vm::with_layout(Layout::new(0x100, 0x10).unwrap());
+    vm.instructions.instructions = vec![
+        // STML a/16 i/16 - Store 16-bit immediate into memory
+        0b01_00_1_1_1_0,
+        // address of 255 in 16 bit little endian, second byte is in the guard
+        0b11111111,
+        0b00000000,
+        2,
+        1,
+    ];
+
+    assert_eq!(
+        vm.run(),
+        Err(VmError::GuardRegion {
+            ic: 0,
+            opcode: 0b01_00_1_1_1_0,
+            address: 0xFF
+        })
+    );
+    assert_eq!(vm.stack.memory()[0xFF], 0);
+}
+
+#[test]
+pub fn it_places_variables_after_the_guard() {
+    let mut vm = Vm::with_layout(Layout::new(0x100, 0x10).unwrap());
+    vm.instructions.instructions = vec![
+        // Stack reset the variable pointer
+        0b10_11_0_0_00,
+        // STR  a/16 r/8  - Store register into memory
+        0b01_00_1_0_0_0,
+        // address of 272 in 16 bit little endian
+        0b00010000,
+        0b00000001,
+        // register r2
+        0b0000_0010,
+    ];
+    vm.registers.r2 = 7;
+    vm.run().unwrap();
+
+    assert_eq!(vm.registers.vp, 0x110);
+    assert_eq!(vm.stack.stack().len(), 0x100);
+    assert_eq!(vm.stack.variable()[0], 7);
+}
+
+#[test]
+pub fn it_uses_the_whole_address_space() {
+    let mut vm = Vm::default();
+    vm.registers.r2 = 9;
+    vm.instructions.instructions = vec![
+        // STR  a/16 r/8  - Store register into memory
+        0b01_00_1_0_0_0,
+        // address of 0xFFFF in 16 bit little endian
+        0b11111111,
+        0b11111111,
+        // register r2
+        0b0000_0010,
+        // STML a/16 i/16 - Store 16-bit immediate into memory
+        0b01_00_1_1_1_0,
+        // address of 0xFFFE in 16 bit little endian
+        0b11111110,
+        0b11111111,
+        2,
+        1,
+    ];
+    vm.run().unwrap();
+
+    assert_eq!(vm.stack.memory().len(), 0x10000);
+    assert_eq!(vm.stack.memory()[0xFFFE], 2);
+    assert_eq!(vm.stack.memory()[0xFFFF], 1);
+}
+
+#[test]
+pub fn it_faults_on_16b_access_past_the_end() {
+    let mut vm = Vm::default();
+    vm.instructions.instructions = vec![
+        // LDML a/16 r/16 - Load 16-bit register from memory
+        0b01_01_1_0_1_0,
+        // address of 0xFFFF in 16 bit little endian
+        0b11111111,
+        0b11111111,
+        // Register l0
+        0b0000_1001,
+    ];
+
+    assert_eq!(
+        vm.run(),
+        Err(VmError::MemoryOutOfRange {
+            ic: 0,
+            opcode: 0b01_01_1_0_1_0,
+            address: 0xFFFF
+        })
+    );
+}
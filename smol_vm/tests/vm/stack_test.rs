@@ -1,4 +1,4 @@
-use smol_vm::Vm;
+use smol_vm::{Stack, Vm};
 
 #[test]
 pub fn it_loads_immediate_variable_address() {
@@ -9,9 +9,9 @@ pub fn it_loads_immediate_variable_address() {
         // Value of 10
         10,
     ];
-    vm.run();
+    vm.run().unwrap();
 
-    assert_eq!(vm.registers.vp, (u16::MAX / 2) + 10);
+    assert_eq!(vm.registers.vp, vm.stack.layout().variable_start() + 10);
 }
 
 #[test]
@@ -24,9 +24,9 @@ pub fn it_loads_immediate_variable_address_16bit() {
         0b00000000,
         0b00000001,
     ];
-    vm.run();
+    vm.run().unwrap();
 
-    assert_eq!(vm.registers.vp, (u16::MAX / 2) + 256);
+    assert_eq!(vm.registers.vp, vm.stack.layout().variable_start() + 256);
 }
 
 #[test]
@@ -39,9 +39,9 @@ pub fn it_loads_register_variable_address() {
         // Register r6
         0b0000_0110,
     ];
-    vm.run();
+    vm.run().unwrap();
 
-    assert_eq!(vm.registers.vp, (u16::MAX / 2) + 5);
+    assert_eq!(vm.registers.vp, vm.stack.layout().variable_start() + 5);
 }
 
 #[test]
@@ -54,14 +54,14 @@ pub fn it_loads_16b_register_variable_address() {
         // Register l1
         0b0000_1010,
     ];
-    vm.run();
-    assert_eq!(vm.registers.vp, (u16::MAX / 2) + 700);
+    vm.run().unwrap();
+    assert_eq!(vm.registers.vp, vm.stack.layout().variable_start() + 700);
 }
 
 #[test]
 pub fn it_resets_variablepointer() {
     let mut vm = Vm::default();
-    vm.registers.vp = (u16::MAX / 2) + 123;
+    vm.registers.vp = vm.stack.layout().variable_start() + 123;
     // First make sure that the SP has been changed
     vm.instructions.instructions = vec![
         // Stack load variable immediate 8bit
@@ -69,8 +69,8 @@ pub fn it_resets_variablepointer() {
         // Value of 10
         10,
     ];
-    vm.run();
-    assert_eq!(vm.registers.vp, (u16::MAX / 2) + 10);
+    vm.run().unwrap();
+    assert_eq!(vm.registers.vp, vm.stack.layout().variable_start() + 10);
 
     vm.registers.ic = 0;
     // Then make sure we actually reset it
@@ -78,8 +78,8 @@ pub fn it_resets_variablepointer() {
         // Stack reset the variable pointer
         0b10_11_0_0_00,
     ];
-    vm.run();
-    assert_eq!(vm.registers.vp, (u16::MAX / 2));
+    vm.run().unwrap();
+    assert_eq!(vm.registers.vp, vm.stack.layout().variable_start());
 }
 
 #[test]
@@ -92,7 +92,7 @@ pub fn it_pushes_register() {
         // Register r3
         0b0000_0011,
     ];
-    vm.run();
+    vm.run().unwrap();
     assert_eq!(vm.registers.sp, 1);
     assert_eq!(vm.stack.memory()[0], 5);
 }
@@ -107,7 +107,7 @@ pub fn it_pushes_register_16b() {
         // Register l0
         0b0000_1001,
     ];
-    vm.run();
+    vm.run().unwrap();
     assert_eq!(vm.registers.sp, 2);
     assert_eq!(vm.stack.memory()[0], 2);
     assert_eq!(vm.stack.memory()[1], 1);
@@ -121,7 +121,7 @@ pub fn it_pushes_immidiate() {
         0b10_00_1_0_00,
         5,
     ];
-    vm.run();
+    vm.run().unwrap();
 
     assert_eq!(vm.registers.sp, 1);
     assert_eq!(vm.stack.memory()[0], 5);
@@ -136,7 +136,7 @@ pub fn it_pushes_immidiate_16b() {
         2,
         1,
     ];
-    vm.run();
+    vm.run().unwrap();
     assert_eq!(vm.registers.sp, 2);
     assert_eq!(vm.stack.memory()[0], 2);
     assert_eq!(vm.stack.memory()[1], 1);
@@ -152,7 +152,7 @@ pub fn it_pops() {
         0b10_01_0_0_00,
         2,
     ];
-    vm.run();
+    vm.run().unwrap();
 
     assert_eq!(vm.registers.sp, 0);
     assert_eq!(vm.registers.r2, 5);
@@ -170,8 +170,21 @@ pub fn it_pops_16b() {
         // Register l0
         0b1001,
     ];
-    vm.run();
+    vm.run().unwrap();
 
     assert_eq!(vm.registers.sp, 0);
     assert_eq!(vm.registers.l0, 258);
 }
+
+#[test]
+pub fn it_bounds_16bit_memory_access() {
+    let mut stack = Stack::default();
+    assert_eq!(stack.save_value_16(0xFFFE, 0x1234), Some(()));
+    assert_eq!(stack.load_value_16(0xFFFE), Some(0x1234));
+    assert_eq!(stack.memory()[0xFFFE..], [0x34, 0x12]);
+
+    // The last byte can't hold a 16-bit value
+    assert_eq!(stack.save_value_16(0xFFFF, 0xABCD), None);
+    assert_eq!(stack.load_value_16(0xFFFF), None);
+    assert_eq!(stack.memory()[0xFFFF], 0x12);
+}
@@ -0,0 +1,65 @@
+use smol_vm::{ExitReason, Vm};
+
+fn add_program() -> Vm {
+    let mut vm = Vm::default();
+    vm.registers.r0 = 1;
+    vm.registers.r1 = 2;
+    vm.instructions.instructions = vec![
+        // ALU Add from Register
+        0b00_000_0_0_0,
+        // Registers r0 and r1
+        0b0001_0000,
+        // ALU Add from Register
+        0b00_000_0_0_0,
+        // Registers r0 and r1
+        0b0001_0000,
+        // ALU Add from Register
+        0b00_000_0_0_0,
+        // Registers r0 and r1
+        0b0001_0000,
+    ];
+    vm
+}
+
+#[test]
+pub fn it_steps_one_instruction() {
+    let mut vm = add_program();
+
+    assert_eq!(vm.step(), Ok(None));
+    assert_eq!(vm.registers.r0, 3);
+    assert_eq!(vm.registers.ic, 2);
+
+    assert_eq!(vm.step(), Ok(None));
+    assert_eq!(vm.step(), Ok(Some(ExitReason::Finished)));
+    assert_eq!(vm.registers.r0, 7);
+
+    // Stepping a finished program doesn't do anything
+    assert_eq!(vm.step(), Ok(Some(ExitReason::Finished)));
+    assert_eq!(vm.registers.r0, 7);
+}
+
+#[test]
+pub fn it_runs_for_instruction_count() {
+    let mut vm = add_program();
+
+    assert_eq!(vm.run_for(2), Ok(ExitReason::InstructionLimit));
+    assert_eq!(vm.registers.r0, 5);
+    assert_eq!(vm.registers.ic, 4);
+
+    assert_eq!(vm.run_for(10), Ok(ExitReason::Finished));
+    assert_eq!(vm.registers.r0, 7);
+}
+
+#[test]
+pub fn it_runs_until_predicate() {
+    let mut vm = add_program();
+
+    assert_eq!(
+        vm.run_until(|vm| vm.registers.ic == 4),
+        Ok(ExitReason::Predicate)
+    );
+    assert_eq!(vm.registers.r0, 5);
+
+    assert_eq!(vm.run_until(|_| false), Ok(ExitReason::Finished));
+    assert_eq!(vm.registers.r0, 7);
+}
@@ -0,0 +1,457 @@
+use std::{cell::RefCell, rc::Rc};
+
+#[cfg(unix)]
+use smol_vm::syscall::Unix;
+
+use smol_vm::{
+    syscall::{
+        guest_buffer, guest_c_str, set_result, DenyAll, SyscallError, SyscallHandler,
+        SyscallOutcome, OPEN_CREATE, OPEN_EXCLUSIVE, OPEN_WRITE, SEEK_END, STAT_SIZE,
+    },
+    ExitReason, Registers, Stack, Vm, VmError, FG_ERROR,
+};
+
+/// Captures the bytes of the write calls
+struct Capture {
+    output: Rc<RefCell<Vec<u8>>>,
+}
+
+impl SyscallHandler for Capture {
+    fn syscall(
+        &mut self,
+        registers: &mut Registers,
+        stack: &mut Stack,
+    ) -> Result<SyscallOutcome, SyscallError> {
+        if registers.r0 != 1 {
+            return Err(SyscallError::NotImplemented(registers.r0));
+        }
+
+        let Some(buf) = guest_buffer(stack, registers.l0 as usize, registers.l1 as usize) else {
+            set_result(registers, Err(14));
+            return Ok(SyscallOutcome::Continue);
+        };
+        self.output.borrow_mut().extend_from_slice(buf);
+        set_result(registers, Ok(registers.l1));
+        Ok(SyscallOutcome::Continue)
+    }
+}
+
+#[test]
+pub fn it_uses_the_syscall_handler() {
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let mut vm = Vm::default();
+    vm.syscalls = Box::new(Capture {
+        output: output.clone(),
+    });
+
+    let vp = vm.stack.layout().variable_start() as usize;
+    vm.stack.memory_mut()[vp..vp + 3].copy_from_slice(b"abc");
+    vm.registers.fg = FG_ERROR;
+    vm.registers.r0 = 1;
+    vm.registers.l0 = vp as u16 + 1;
+    vm.registers.l1 = 2;
+    vm.instructions.instructions = vec![
+        // Syscall
+        0b11_101_1_1_1,
+    ];
+    vm.run().unwrap();
+
+    assert_eq!(*output.borrow(), b"bc");
+    assert_eq!(vm.registers.l0, 2);
+    assert_eq!(vm.registers.fg & FG_ERROR, 0);
+}
+
+#[test]
+pub fn it_denies_every_syscall() {
+    let mut vm = Vm::default();
+    vm.syscalls = Box::new(DenyAll);
+    vm.registers.r0 = 1;
+    vm.instructions.instructions = vec![
+        // Syscall
+        0b11_101_1_1_1,
+    ];
+
+    assert_eq!(
+        vm.run(),
+        Err(VmError::UnimplementedSyscall {
+            ic: 0,
+            opcode: 0b11_101_1_1_1,
+            id: 1
+        })
+    );
+}
+
+#[cfg(unix)]
+#[test]
+pub fn it_halts_on_exit() {
+    let mut vm = Vm::default();
+    vm.instructions.instructions = vec![
+        // STI  r/8  i/8  - Store exit syscall id in r0
+        0b01_00_0_1_0_0,
+        0b0000_0000,
+        60,
+        // STI  r/8  i/8  - Store exit status in r1
+        0b01_00_0_1_0_0,
+        0b0000_0001,
+        3,
+        // Syscall
+        0b11_101_1_1_1,
+        // ALU Increment from Register r2, never executed
+        0b00_111_0_0_0,
+        0b0000_0010,
+    ];
+
+    assert_eq!(vm.run(), Ok(ExitReason::Exited(3)));
+    assert_eq!(vm.exit_status(), Some(3));
+    assert_eq!(vm.registers.ic, 7);
+    assert_eq!(vm.registers.r2, 0);
+
+    // Halted VM doesn't continue
+    assert_eq!(vm.step(), Ok(Some(ExitReason::Exited(3))));
+    assert_eq!(vm.registers.ic, 7);
+}
+
+/// Run a single syscall instruction from the start
+#[cfg(unix)]
+fn run_syscall(vm: &mut Vm) {
+    vm.registers.ic = 0;
+    vm.instructions.instructions = vec![
+        // Syscall
+        0b11_101_1_1_1,
+    ];
+    vm.run().unwrap();
+}
+
+/// Errno of the failed call
+#[cfg(unix)]
+fn syscall_error(vm: &Vm) -> Option<i32> {
+    (vm.registers.fg & FG_ERROR != 0).then_some(vm.registers.l0 as i32)
+}
+
+#[cfg(unix)]
+#[test]
+pub fn it_rejects_write_past_the_memory() {
+    let mut vm = Vm::default();
+    vm.registers.r0 = 1;
+    vm.registers.r1 = 1;
+    vm.registers.l0 = 0xFFFF;
+    vm.registers.l1 = 2;
+    run_syscall(&mut vm);
+
+    assert_eq!(syscall_error(&vm), Some(libc::EFAULT));
+}
+
+#[cfg(unix)]
+#[test]
+pub fn it_rejects_read_into_the_guard_region() {
+    let mut vm = Vm::default();
+    let guard = vm.stack.layout().guard();
+    vm.registers.r0 = 0;
+    vm.registers.r1 = 0;
+    vm.registers.l0 = guard.start as u16 - 1;
+    vm.registers.l1 = 2;
+    run_syscall(&mut vm);
+
+    assert_eq!(syscall_error(&vm), Some(libc::EFAULT));
+}
+
+#[cfg(unix)]
+#[test]
+pub fn it_rejects_unterminated_path() {
+    let mut vm = Vm::default();
+    vm.stack.memory_mut()[0xFFF0..].fill(b'a');
+    vm.registers.r0 = 2;
+    vm.registers.l0 = 0xFFF0;
+    run_syscall(&mut vm);
+
+    assert_eq!(syscall_error(&vm), Some(libc::EFAULT));
+}
+
+#[cfg(unix)]
+#[test]
+pub fn it_rejects_unknown_open_flags() {
+    let mut vm = Vm::default();
+    vm.stack.memory_mut()[0x8000..0x800A].copy_from_slice(b"/dev/null\0");
+    vm.registers.r0 = 2;
+    vm.registers.r2 = 1 << 7;
+    vm.registers.l0 = 0x8000;
+    run_syscall(&mut vm);
+
+    assert_eq!(syscall_error(&vm), Some(libc::EINVAL));
+}
+
+#[cfg(unix)]
+#[test]
+pub fn it_opens_and_closes_a_file() {
+    let mut vm = Vm::default();
+    vm.stack.memory_mut()[0x8000..0x800A].copy_from_slice(b"/dev/null\0");
+    vm.registers.r0 = 2;
+    vm.registers.r2 = OPEN_WRITE;
+    vm.registers.l0 = 0x8000;
+    run_syscall(&mut vm);
+    assert_eq!(syscall_error(&vm), None);
+    let fd = vm.registers.l0 as u8;
+
+    // Writing a buffer longer than 255 bytes returns the full count
+    vm.registers.r0 = 1;
+    vm.registers.r1 = fd;
+    vm.registers.l0 = 0x8000;
+    vm.registers.l1 = 300;
+    run_syscall(&mut vm);
+    assert_eq!(syscall_error(&vm), None);
+    assert_eq!(vm.registers.l0, 300);
+
+    vm.registers.r0 = 3;
+    run_syscall(&mut vm);
+    assert_eq!(syscall_error(&vm), None);
+    assert_eq!(vm.registers.l0, 0);
+
+    // Closing again fails with the errno of the host
+    vm.registers.r0 = 3;
+    run_syscall(&mut vm);
+    assert_eq!(syscall_error(&vm), Some(libc::EBADF));
+}
+
+#[cfg(unix)]
+#[test]
+pub fn it_creates_files_with_mode() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = std::env::temp_dir().join(format!("smol-syscall-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("created");
+    let _ = std::fs::remove_file(&path);
+    let mut c_path = path.to_str().unwrap().as_bytes().to_vec();
+    c_path.push(0);
+
+    let mut vm = Vm::default();
+    vm.stack.memory_mut()[0x8000..0x8000 + c_path.len()].copy_from_slice(&c_path);
+    vm.registers.r0 = 2;
+    vm.registers.r2 = OPEN_WRITE | OPEN_CREATE | OPEN_EXCLUSIVE;
+    vm.registers.l0 = 0x8000;
+    vm.registers.l1 = 0o600;
+    run_syscall(&mut vm);
+    assert_eq!(syscall_error(&vm), None);
+
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+
+    // Exclusive create fails once the file exists
+    vm.registers.l0 = 0x8000;
+    run_syscall(&mut vm);
+    assert_eq!(syscall_error(&vm), Some(libc::EEXIST));
+}
+
+#[test]
+pub fn it_bounds_guest_buffers() {
+    let mut vm = Vm::default();
+    let guard = vm.stack.layout().guard();
+    vm.stack.memory_mut()[0xFFFE] = b'x';
+
+    assert_eq!(guest_buffer(&vm.stack, 0xFFFE, 2), Some(&[b'x', 0][..]));
+    assert_eq!(guest_buffer(&vm.stack, 0xFFFE, 3), None);
+    assert_eq!(guest_buffer(&vm.stack, usize::MAX, 2), None);
+    assert_eq!(guest_buffer(&vm.stack, guard.start - 1, 2), None);
+    assert_eq!(guest_c_str(&vm.stack, 0xFFFE), Some(c"x"));
+    assert_eq!(guest_c_str(&vm.stack, 0xFFFF), Some(c""));
+    assert_eq!(guest_c_str(&vm.stack, guard.start), None);
+
+    vm.stack.memory_mut()[0xFFFF] = b'y';
+    assert_eq!(guest_c_str(&vm.stack, 0xFFFE), None);
+}
+
+/// Temporary file with the contents, NUL terminated path is written to `addr`
+#[cfg(unix)]
+fn temp_file(vm: &mut Vm, name: &str, contents: &[u8], addr: usize) {
+    let dir = std::env::temp_dir().join(format!("smol-syscall-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    std::fs::write(&path, contents).unwrap();
+
+    let mut c_path = path.to_str().unwrap().as_bytes().to_vec();
+    c_path.push(0);
+    vm.stack.memory_mut()[addr..addr + c_path.len()].copy_from_slice(&c_path);
+}
+
+#[cfg(unix)]
+#[test]
+pub fn it_stats_files() {
+    let mut vm = Vm::default();
+    temp_file(&mut vm, "stat", &[7; 300], 0x8000);
+    vm.registers.r0 = 4;
+    vm.registers.l0 = 0x8000;
+    vm.registers.cr = 0x9000;
+    run_syscall(&mut vm);
+    assert_eq!(syscall_error(&vm), None);
+
+    let stat = &vm.stack.memory()[0x9000..0x9000 + STAT_SIZE as usize];
+    assert_eq!(u32::from_le_bytes(stat[0..4].try_into().unwrap()), 300);
+    let mode = u16::from_le_bytes([stat[4], stat[5]]);
+    assert_eq!(u32::from(mode) & libc::S_IFMT, libc::S_IFREG);
+
+    // Open the file and stat the descriptor
+    vm.registers.r0 = 2;
+    vm.registers.r2 = 0;
+    vm.registers.l0 = 0x8000;
+    run_syscall(&mut vm);
+    let fd = vm.registers.l0 as u8;
+
+    vm.registers.r0 = 5;
+    vm.registers.r1 = fd;
+    vm.registers.l0 = 0x9100;
+    run_syscall(&mut vm);
+    assert_eq!(syscall_error(&vm), None);
+    assert_eq!(
+        vm.stack.memory()[0x9100..0x9100 + STAT_SIZE as usize],
+        vm.stack.memory()[0x9000..0x9000 + STAT_SIZE as usize]
+    );
+
+    // Missing file
+    vm.stack.memory_mut()[0x8000..0x8002].copy_from_slice(b"\x01\0");
+    vm.registers.r0 = 4;
+    vm.registers.l0 = 0x8000;
+    run_syscall(&mut vm);
+    assert_eq!(syscall_error(&vm), Some(libc::ENOENT));
+}
+
+#[cfg(unix)]
+#[test]
+pub fn it_seeks_files() {
+    let mut vm = Vm::default();
+    temp_file(&mut vm, "seek", b"0123456789", 0x8000);
+    vm.registers.r0 = 2;
+    vm.registers.r2 = 0;
+    vm.registers.l0 = 0x8000;
+    run_syscall(&mut vm);
+    let fd = vm.registers.l0 as u8;
+
+    // Seek to 3 bytes before the end
+    vm.registers.r0 = 8;
+    vm.registers.r1 = fd;
+    vm.registers.r2 = SEEK_END;
+    vm.registers.l0 = -3i16 as u16;
+    vm.registers.cr = 0xFFFF;
+    run_syscall(&mut vm);
+    assert_eq!(syscall_error(&vm), None);
+    assert_eq!((vm.registers.cr, vm.registers.l0), (0, 7));
+
+    vm.registers.r0 = 0;
+    vm.registers.l0 = 0x9000;
+    vm.registers.l1 = 10;
+    run_syscall(&mut vm);
+    assert_eq!(vm.registers.l0, 3);
+    assert_eq!(vm.stack.memory()[0x9000..0x9003], *b"789");
+
+    vm.registers.r0 = 8;
+    vm.registers.r2 = 7;
+    run_syscall(&mut vm);
+    assert_eq!(syscall_error(&vm), Some(libc::EINVAL));
+}
+
+#[cfg(unix)]
+#[test]
+pub fn it_reads_process_and_time() {
+    let mut vm = Vm::default();
+    vm.registers.r0 = 39;
+    run_syscall(&mut vm);
+    let pid = ((vm.registers.cr as u32) << 16) | vm.registers.l0 as u32;
+    assert_eq!(pid, std::process::id());
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as u32;
+    vm.registers.r0 = 201;
+    run_syscall(&mut vm);
+    let time = ((vm.registers.cr as u32) << 16) | vm.registers.l0 as u32;
+    assert!(time >= now && time - now <= 1);
+
+    // Clock advances at least as much as the sleep
+    vm.registers.r0 = 202;
+    run_syscall(&mut vm);
+    let before = ((vm.registers.cr as u32) << 16) | vm.registers.l0 as u32;
+
+    vm.registers.r0 = 35;
+    vm.registers.l0 = 5;
+    run_syscall(&mut vm);
+    assert_eq!(syscall_error(&vm), None);
+
+    vm.registers.r0 = 202;
+    run_syscall(&mut vm);
+    let after = ((vm.registers.cr as u32) << 16) | vm.registers.l0 as u32;
+    assert!(after - before >= 5);
+}
+
+#[cfg(unix)]
+#[test]
+pub fn it_fills_random_bytes() {
+    let mut vm = Vm::default();
+    vm.registers.r0 = 100;
+    vm.registers.l0 = 0x8000;
+    vm.registers.l1 = 64;
+    run_syscall(&mut vm);
+
+    assert_eq!(syscall_error(&vm), None);
+    assert_eq!(vm.registers.l0, 64);
+    assert!(vm.stack.memory()[0x8000..0x8040]
+        .iter()
+        .any(|byte| *byte != 0));
+    assert!(vm.stack.memory()[0x8040..0x8080]
+        .iter()
+        .all(|byte| *byte == 0));
+}
+
+#[cfg(unix)]
+#[test]
+pub fn it_reads_arguments() {
+    let mut vm = Vm::default();
+    vm.syscalls = Box::new(Unix::new(["prog.obj", "input.txt"]));
+    vm.registers.r0 = 101;
+    run_syscall(&mut vm);
+    assert_eq!(vm.registers.l0, 2);
+
+    vm.registers.r0 = 102;
+    vm.registers.r1 = 1;
+    vm.registers.l0 = 0x8000;
+    vm.registers.l1 = 16;
+    run_syscall(&mut vm);
+    assert_eq!(syscall_error(&vm), None);
+    assert_eq!(vm.registers.l0, 9);
+    assert_eq!(vm.stack.memory()[0x8000..0x800A], *b"input.txt\0");
+
+    // Too small buffer for the terminator
+    vm.registers.l0 = 0x8000;
+    vm.registers.l1 = 9;
+    run_syscall(&mut vm);
+    assert_eq!(syscall_error(&vm), Some(libc::ERANGE));
+
+    vm.registers.r1 = 2;
+    vm.registers.l1 = 16;
+    run_syscall(&mut vm);
+    assert_eq!(syscall_error(&vm), Some(libc::EINVAL));
+}
+
+#[cfg(unix)]
+#[test]
+pub fn it_reads_environment() {
+    let expected = std::env::var("PATH").unwrap();
+    let mut vm = Vm::default();
+    vm.stack.memory_mut()[0x8000..0x8005].copy_from_slice(b"PATH\0");
+    vm.registers.r0 = 103;
+    vm.registers.l0 = 0x8000;
+    vm.registers.cr = 0x9000;
+    vm.registers.l1 = 0x1000;
+    run_syscall(&mut vm);
+
+    assert_eq!(syscall_error(&vm), None);
+    assert_eq!(vm.registers.l0 as usize, expected.len());
+    assert_eq!(
+        vm.stack.memory()[0x9000..0x9000 + expected.len()],
+        *expected.as_bytes()
+    );
+
+    vm.stack.memory_mut()[0x8000..0x8012].copy_from_slice(b"SMOL_SURELY_UNSET\0");
+    vm.registers.l0 = 0x8000;
+    run_syscall(&mut vm);
+    assert_eq!(syscall_error(&vm), Some(libc::ENOENT));
+}